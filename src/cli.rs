@@ -0,0 +1,607 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::daemon;
+use crate::formatter::{Placeholder, WorkspaceFormat, WorkspaceSort, WorkspaceView, YuckFormat};
+use crate::icons::Icons;
+use crate::ipc_client::{self, IPCClient, IPCError, WindowManager};
+use crate::template::{self, Template, TemplateError, WorkspaceTemplate};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    // the default when no subcommand is given
+    ListenWorkspaces,
+    GetWorkspaces,
+    WindowTitle,
+    Mode,
+    // optionally restricted to the keyboard with this identifier
+    Input(Option<String>),
+    Daemon,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    // how many times in a row to try reconnecting before giving up, None retries forever
+    pub max_retries: Option<u32>,
+    // printed once whenever the connection to sway is lost
+    pub disconnected_line: String,
+    // takes priority over SWAYSOCK and friends
+    pub socket: Option<PathBuf>,
+    // logs diagnostics to stderr, stdout is reserved for eww
+    pub verbose: bool,
+    // replies from sway longer than this many bytes are treated as a corrupt stream
+    pub max_payload_len: u32,
+    // None detects it from GET_VERSION on every connect
+    pub wm: Option<WindowManager>,
+    // attach to a running daemon instead of connecting to sway directly
+    pub use_daemon: bool,
+    // how long the workspace list is patched from events before it is fetched in full again
+    pub resync_interval: Duration,
+    // events arriving within this long of each other are printed as a single line, zero prints
+    // every event
+    pub debounce: Duration,
+    // the longest a burst of events can hold a line back, None waits for the burst to end
+    pub debounce_max: Option<Duration>,
+    // print every line even if it is the same as the previous one
+    pub always_emit: bool,
+    // --format, --output and --per-output for the workspace list
+    pub view: WorkspaceView,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            command: Command::ListenWorkspaces,
+            max_retries: None,
+            disconnected_line: String::from("disconnected"),
+            socket: None,
+            verbose: false,
+            max_payload_len: ipc_client::DEFAULT_MAX_PAYLOAD_LEN,
+            wm: None,
+            use_daemon: true,
+            resync_interval: Duration::from_secs(60),
+            debounce: Duration::ZERO,
+            debounce_max: None,
+            always_emit: false,
+            view: WorkspaceView::default(),
+        }
+    }
+}
+
+impl Options {
+    // Connects a client for queries and returns it with the path, for the event connection. A
+    // running daemon is preferred unless a socket was given explicitly. It is only tried when its
+    // socket exists, and sway is used when connecting to it fails as a crashed daemon leaves the
+    // socket behind. Resolved again on every (re)connect as sway gets a new socket when it
+    // restarts.
+    pub fn connect(&self) -> Result<(IPCClient, PathBuf), IPCError> {
+        if self.use_daemon && self.socket.is_none() && self.command != Command::Daemon {
+            if let Some(path) = daemon::daemon_socket_path().filter(|path| path.exists()) {
+                if self.verbose {
+                    eprintln!("using daemon {}", path.display());
+                }
+                match self.connect_to(&path) {
+                    Ok(client) => return Ok((client, path)),
+                    Err(e) if self.verbose => eprintln!("the daemon did not answer: {e}"),
+                    Err(_) => {}
+                }
+            }
+        }
+        let path = ipc_client::find_socket_path(self.socket.as_deref(), self.wm)?;
+        if self.verbose {
+            eprintln!("using socket {}", path.display());
+        }
+        Ok((self.connect_to(&path)?, path))
+    }
+
+    // Asks the window manager what it is unless --wm was given
+    fn connect_to(&self, path: &Path) -> Result<IPCClient, IPCError> {
+        let client = IPCClient::connect_to(path)?.max_payload_len(self.max_payload_len);
+        let wm = match self.wm {
+            Some(wm) => wm,
+            None => client.detect_wm()?,
+        };
+        if self.verbose {
+            eprintln!("talking to {}", wm.name());
+        }
+        Ok(client.wm(wm))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ArgError {
+    UnknownCommand(String),
+    UnknownFlag(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+    InvalidTemplate(String, TemplateError),
+    // a file given to a flag could not be read or parsed
+    ConfigError(PathBuf, String),
+    // two flags that choose different output formats
+    ConflictingFlags(String, String),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgError::UnknownCommand(command) => write!(f, "unknown command \"{command}\""),
+            ArgError::UnknownFlag(flag) => write!(f, "unknown flag \"{flag}\""),
+            ArgError::MissingValue(flag) => write!(f, "{flag} needs a value"),
+            ArgError::InvalidValue(flag, value) => {
+                write!(f, "invalid value \"{value}\" for {flag}")
+            }
+            ArgError::InvalidTemplate(flag, e) => write!(f, "invalid template for {flag}: {e}"),
+            ArgError::ConfigError(path, e) => write!(f, "cannot use {}: {e}", path.display()),
+            ArgError::ConflictingFlags(flag, other) => {
+                write!(f, "{flag} cannot be combined with {other}")
+            }
+        }
+    }
+}
+
+// "15ms", "1s" or a plain number of milliseconds
+fn parse_duration(value: &str) -> Option<Duration> {
+    if let Some(ms) = value.strip_suffix("ms") {
+        return ms.parse().ok().map(Duration::from_millis);
+    }
+    if let Some(s) = value.strip_suffix('s') {
+        return s.parse().ok().map(Duration::from_secs);
+    }
+    value.parse().ok().map(Duration::from_millis)
+}
+
+// args[0] is the program name, flags can appear before or after the subcommand
+pub fn parse_args(args: &[String]) -> Result<Options, ArgError> {
+    let mut options = Options::default();
+    let mut positional: Vec<&String> = vec![];
+    // put together after all flags are read so they can come in any order
+    let mut template: Option<&String> = None;
+    let mut join: Option<&String> = None;
+    let mut wrap: Option<&String> = None;
+    let mut escape: Option<bool> = None;
+    let mut format: Option<&String> = None;
+    let mut onclick: Option<&String> = None;
+    let mut class: Option<&String> = None;
+    let mut focused_class: Option<&String> = None;
+    let mut visible_class: Option<&String> = None;
+    let mut urgent_class: Option<&String> = None;
+    let mut empty_class: Option<&String> = None;
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg);
+            continue;
+        }
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| ArgError::MissingValue(arg.to_string()))
+        };
+        match arg.as_str() {
+            "--max-retries" => {
+                let retries = value()?;
+                options.max_retries =
+                    Some(retries.parse().map_err(|_| {
+                        ArgError::InvalidValue(arg.to_string(), retries.to_string())
+                    })?);
+            }
+            "--disconnected" => options.disconnected_line = value()?.to_string(),
+            "--socket" => options.socket = Some(PathBuf::from(value()?)),
+            "--verbose" => options.verbose = true,
+            "--no-daemon" => options.use_daemon = false,
+            "--always-emit" => options.always_emit = true,
+            "--output" => options.view.output = Some(value()?.to_string()),
+            "--per-output" => options.view.per_output = true,
+            "--strip-number" => options.view.strip_number = true,
+            "--icons" => {
+                let path = PathBuf::from(value()?);
+                let icons = Icons::load(&path).map_err(|e| ArgError::ConfigError(path, e))?;
+                options.view.icons = Some(icons);
+            }
+            "--sort" => {
+                options.view.sort = match value()?.as_str() {
+                    "sway" => WorkspaceSort::Sway,
+                    "num" => WorkspaceSort::Num,
+                    "name" => WorkspaceSort::Name,
+                    "output" => WorkspaceSort::Output,
+                    sort => return Err(ArgError::InvalidValue(arg.to_string(), sort.to_string())),
+                }
+            }
+            "--persistent" => {
+                let list = value()?;
+                let placeholders = Placeholder::parse_list(list)
+                    .ok_or_else(|| ArgError::InvalidValue(arg.to_string(), list.to_string()))?;
+                options.view.persistent.extend(placeholders);
+            }
+            "--format" => {
+                let name = value()?;
+                format = Some(name);
+                options.view.format = match name.as_str() {
+                    "names" => WorkspaceFormat::Names,
+                    "objects" => WorkspaceFormat::Objects,
+                    "yuck" => WorkspaceFormat::Yuck(YuckFormat::default()),
+                    format => {
+                        return Err(ArgError::InvalidValue(arg.to_string(), format.to_string()))
+                    }
+                }
+            }
+            "--debounce" => {
+                let debounce = value()?;
+                options.debounce = parse_duration(debounce)
+                    .ok_or_else(|| ArgError::InvalidValue(arg.to_string(), debounce.to_string()))?;
+            }
+            "--debounce-max" => {
+                let max = value()?;
+                options.debounce_max = Some(
+                    parse_duration(max)
+                        .ok_or_else(|| ArgError::InvalidValue(arg.to_string(), max.to_string()))?,
+                );
+            }
+            "--template" => template = Some(value()?),
+            "--join" => join = Some(value()?),
+            "--wrap" => wrap = Some(value()?),
+            "--escape" => {
+                escape = match value()?.as_str() {
+                    "json" => Some(true),
+                    "none" => Some(false),
+                    escape => {
+                        return Err(ArgError::InvalidValue(arg.to_string(), escape.to_string()))
+                    }
+                }
+            }
+            "--onclick" => onclick = Some(value()?),
+            "--class" => class = Some(value()?),
+            "--focused-class" => focused_class = Some(value()?),
+            "--visible-class" => visible_class = Some(value()?),
+            "--urgent-class" => urgent_class = Some(value()?),
+            "--empty-class" => empty_class = Some(value()?),
+            "--resync" => {
+                let seconds = value()?;
+                options.resync_interval =
+                    Duration::from_secs(seconds.parse().map_err(|_| {
+                        ArgError::InvalidValue(arg.to_string(), seconds.to_string())
+                    })?);
+            }
+            "--wm" => {
+                options.wm = match value()?.as_str() {
+                    "sway" => Some(WindowManager::Sway),
+                    "i3" => Some(WindowManager::I3),
+                    "auto" => None,
+                    wm => return Err(ArgError::InvalidValue(arg.to_string(), wm.to_string())),
+                }
+            }
+            "--max-payload" => {
+                let len = value()?;
+                options.max_payload_len = len
+                    .parse()
+                    .map_err(|_| ArgError::InvalidValue(arg.to_string(), len.to_string()))?;
+            }
+            _ => return Err(ArgError::UnknownFlag(arg.to_string())),
+        }
+    }
+
+    // the template and yuck flags each imply their format, so they cannot be mixed with each
+    // other or with a --format that chooses another one
+    let template_flag = [
+        ("--template", template.is_some()),
+        ("--join", join.is_some()),
+        ("--wrap", wrap.is_some()),
+        ("--escape", escape.is_some()),
+    ]
+    .into_iter()
+    .find_map(|(flag, given)| given.then_some(flag));
+    let yuck_flag = [
+        ("--onclick", onclick),
+        ("--class", class),
+        ("--focused-class", focused_class),
+        ("--visible-class", visible_class),
+        ("--urgent-class", urgent_class),
+        ("--empty-class", empty_class),
+    ]
+    .into_iter()
+    .find_map(|(flag, value)| value.map(|_| flag));
+    let conflict =
+        |flag: String, other: &str| Err(ArgError::ConflictingFlags(flag, other.to_string()));
+    if let (Some(flag), Some(other)) = (template_flag, yuck_flag) {
+        return conflict(flag.to_string(), other);
+    }
+    if let Some(format) = format {
+        if let Some(other) = template_flag {
+            return conflict(format!("--format {format}"), other);
+        }
+        if let Some(other) = yuck_flag.filter(|_| format != "yuck") {
+            return conflict(format!("--format {format}"), other);
+        }
+    }
+
+    if template_flag.is_some() {
+        let template = template.map_or("{name}", |template| template.as_str());
+        let join = join.map_or("", |join| join.as_str());
+        let wrap = wrap.map_or("{items}", |wrap| wrap.as_str());
+        // checked on its own first so the error names the right flag
+        if let Err(e) = Template::parse(wrap, &template::WRAPPER_FIELDS) {
+            return Err(ArgError::InvalidTemplate(String::from("--wrap"), e));
+        }
+        let mut template = WorkspaceTemplate::new(template, join, wrap)
+            .map_err(|e| ArgError::InvalidTemplate(String::from("--template"), e))?;
+        if escape == Some(true) {
+            template = template.json();
+        }
+        options.view.format = WorkspaceFormat::Template(template);
+    }
+
+    if yuck_flag.is_some() {
+        let mut yuck = YuckFormat::default();
+        if let Some(onclick) = onclick {
+            yuck = yuck
+                .onclick(onclick)
+                .map_err(|e| ArgError::InvalidTemplate(String::from("--onclick"), e))?;
+        }
+        if let Some(class) = class {
+            yuck.class = class.to_string();
+        }
+        if let Some(class) = focused_class {
+            yuck.focused_class = class.to_string();
+        }
+        if let Some(class) = visible_class {
+            yuck.visible_class = class.to_string();
+        }
+        if let Some(class) = urgent_class {
+            yuck.urgent_class = class.to_string();
+        }
+        if let Some(class) = empty_class {
+            yuck.empty_class = class.to_string();
+        }
+        options.view.format = WorkspaceFormat::Yuck(yuck);
+    }
+
+    let mut positional = positional.into_iter();
+    options.command = match positional.next().map(|command| command.as_str()) {
+        None => Command::ListenWorkspaces,
+        Some("get-workspaces") => Command::GetWorkspaces,
+        Some("window-title") => Command::WindowTitle,
+        Some("mode") => Command::Mode,
+        Some("input") => Command::Input(positional.next().cloned()),
+        Some("daemon") => Command::Daemon,
+        Some(command) => return Err(ArgError::UnknownCommand(command.to_string())),
+    };
+    Ok(options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn no_args_listen_to_workspaces() {
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend")).unwrap(),
+            Options::default()
+        );
+    }
+
+    #[test]
+    fn flags_go_before_or_after_the_command() {
+        let options = parse_args(&args(
+            "eww_sway_ipc_backend --max-retries 5 input kbd --disconnected []",
+        ))
+        .unwrap();
+        assert_eq!(options.command, Command::Input(Some(String::from("kbd"))));
+        assert_eq!(options.max_retries, Some(5));
+        assert_eq!(options.disconnected_line, "[]");
+    }
+
+    #[test]
+    fn parses_connection_flags() {
+        let options = parse_args(&args(
+            "eww_sway_ipc_backend mode --verbose --socket /run/user/1000/sway-ipc.sock",
+        ))
+        .unwrap();
+        assert_eq!(options.command, Command::Mode);
+        assert!(options.verbose);
+        assert_eq!(
+            options.socket,
+            Some(PathBuf::from("/run/user/1000/sway-ipc.sock"))
+        );
+
+        let options = parse_args(&args("eww_sway_ipc_backend --max-payload 1048576")).unwrap();
+        assert_eq!(options.max_payload_len, 1 << 20);
+    }
+
+    #[test]
+    fn parses_wm() {
+        let options = parse_args(&args("eww_sway_ipc_backend window-title --wm i3")).unwrap();
+        assert_eq!(options.wm, Some(WindowManager::I3));
+        let options = parse_args(&args("eww_sway_ipc_backend --wm auto")).unwrap();
+        assert_eq!(options.wm, None);
+    }
+
+    #[test]
+    fn parses_daemon_flags() {
+        let options = parse_args(&args("eww_sway_ipc_backend daemon --no-daemon")).unwrap();
+        assert_eq!(options.command, Command::Daemon);
+        assert!(!options.use_daemon);
+    }
+
+    #[test]
+    fn parses_resync() {
+        let options = parse_args(&args("eww_sway_ipc_backend --resync 0")).unwrap();
+        assert_eq!(options.resync_interval, Duration::ZERO);
+    }
+
+    #[test]
+    fn parses_debounce_and_always_emit() {
+        let options = parse_args(&args(
+            "eww_sway_ipc_backend --debounce 15ms --debounce-max 100ms --always-emit",
+        ))
+        .unwrap();
+        assert_eq!(options.debounce, Duration::from_millis(15));
+        assert_eq!(options.debounce_max, Some(Duration::from_millis(100)));
+        assert!(options.always_emit);
+    }
+
+    #[test]
+    fn durations_take_a_unit() {
+        assert_eq!(parse_duration("15ms"), Some(Duration::from_millis(15)));
+        assert_eq!(parse_duration("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_duration("40"), Some(Duration::from_millis(40)));
+        assert_eq!(parse_duration("fast"), None);
+    }
+
+    #[test]
+    fn parses_format_and_outputs() {
+        let options = parse_args(&args(
+            "eww_sway_ipc_backend --format objects --output HDMI-A-1 --per-output",
+        ))
+        .unwrap();
+        assert_eq!(
+            options.view,
+            WorkspaceView {
+                format: WorkspaceFormat::Objects,
+                output: Some(String::from("HDMI-A-1")),
+                per_output: true,
+                persistent: vec![],
+                sort: WorkspaceSort::Sway,
+                strip_number: false,
+                icons: None,
+            }
+        );
+    }
+
+    #[test]
+    fn template_flags_imply_a_template() {
+        let options = parse_args(&args(
+            "eww_sway_ipc_backend --join , --template {name}{?focused:*} --wrap [{items}]",
+        ))
+        .unwrap();
+        assert_eq!(
+            options.view.format,
+            WorkspaceFormat::Template(
+                WorkspaceTemplate::new("{name}{?focused:*}", ",", "[{items}]").unwrap()
+            )
+        );
+        let options = parse_args(&args(
+            "eww_sway_ipc_backend --template \"{name}\" --escape json",
+        ))
+        .unwrap();
+        assert_eq!(
+            options.view.format,
+            WorkspaceFormat::Template(
+                WorkspaceTemplate::new("\"{name}\"", "", "{items}")
+                    .unwrap()
+                    .json()
+            )
+        );
+    }
+
+    #[test]
+    fn yuck_flags_imply_yuck() {
+        let options = parse_args(&args(
+            "eww_sway_ipc_backend --onclick swaymsg_workspace_{num} --focused-class active",
+        ))
+        .unwrap();
+        let mut yuck = YuckFormat::default()
+            .onclick("swaymsg_workspace_{num}")
+            .unwrap();
+        yuck.focused_class = String::from("active");
+        assert_eq!(options.view.format, WorkspaceFormat::Yuck(yuck));
+    }
+
+    #[test]
+    fn parses_persistent_sort_and_strip_number() {
+        let options = parse_args(&args(
+            "eww_sway_ipc_backend --persistent 1-2@eDP-1 --sort num --persistent mail --strip-number",
+        ))
+        .unwrap();
+        assert_eq!(options.view.sort, WorkspaceSort::Num);
+        assert!(options.view.strip_number);
+        let names: Vec<(&str, Option<&str>)> = options
+            .view
+            .persistent
+            .iter()
+            .map(|placeholder| (placeholder.name.as_str(), placeholder.output.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![("1", Some("eDP-1")), ("2", Some("eDP-1")), ("mail", None)]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_args() {
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend workspaces")).unwrap_err(),
+            ArgError::UnknownCommand(String::from("workspaces"))
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --max-retries")).unwrap_err(),
+            ArgError::MissingValue(String::from("--max-retries"))
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --max-retries lots")).unwrap_err(),
+            ArgError::InvalidValue(String::from("--max-retries"), String::from("lots"))
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --loud")).unwrap_err(),
+            ArgError::UnknownFlag(String::from("--loud"))
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --wm hyprland")).unwrap_err(),
+            ArgError::InvalidValue(String::from("--wm"), String::from("hyprland"))
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --template {title}")).unwrap_err(),
+            ArgError::InvalidTemplate(
+                String::from("--template"),
+                TemplateError::UnknownField(String::from("title"))
+            )
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --format yuck --onclick {?num}")).unwrap_err(),
+            ArgError::InvalidTemplate(
+                String::from("--onclick"),
+                TemplateError::MissingColon(String::from("num"))
+            )
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --persistent 9-4")).unwrap_err(),
+            ArgError::InvalidValue(String::from("--persistent"), String::from("9-4"))
+        );
+        assert!(matches!(
+            parse_args(&args(
+                "eww_sway_ipc_backend --icons /nonexistent/icons.json"
+            ))
+            .unwrap_err(),
+            ArgError::ConfigError(..)
+        ));
+        // flags choosing different output formats
+        assert_eq!(
+            parse_args(&args(
+                "eww_sway_ipc_backend --format objects --template {name}"
+            ))
+            .unwrap_err(),
+            ArgError::ConflictingFlags(
+                String::from("--format objects"),
+                String::from("--template")
+            )
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --escape json --format yuck")).unwrap_err(),
+            ArgError::ConflictingFlags(String::from("--format yuck"), String::from("--escape"))
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --format names --class ws")).unwrap_err(),
+            ArgError::ConflictingFlags(String::from("--format names"), String::from("--class"))
+        );
+        assert_eq!(
+            parse_args(&args("eww_sway_ipc_backend --join , --onclick true")).unwrap_err(),
+            ArgError::ConflictingFlags(String::from("--join"), String::from("--onclick"))
+        );
+        assert!(parse_args(&args("eww_sway_ipc_backend --format yuck --class ws")).is_ok());
+    }
+}
@@ -0,0 +1,667 @@
+use std::env;
+use std::fs;
+use std::net::Shutdown;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::cli::Options;
+use crate::ipc_client::{
+    self, IPCClient, IPCError, IPCEvents, IPCFormat, IPCMessages, Subscription, WindowManager,
+};
+use crate::ipc_types::ModeEvent;
+use crate::{ipc_types, listener};
+
+// Subscribers that stop reading are dropped instead of stalling everyone else. Their events wait
+// in a queue of their own, and once it is full or a write times out they are disconnected,
+// which makes them reconnect.
+const SUBSCRIBER_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+const SUBSCRIBER_QUEUE_LEN: usize = 64;
+
+// Far more than any number of bars needs, connections beyond it are closed right away
+const MAX_CLIENTS: usize = 64;
+
+// Where the daemon listens, next to the sway socket. Anyone who can create the socket could
+// pose as the daemon, so there is none unless XDG_RUNTIME_DIR is set to a directory that belongs
+// to us and is closed to everyone else, as the one systemd-logind creates is.
+pub fn daemon_socket_path() -> Option<PathBuf> {
+    let runtime_dir = PathBuf::from(env::var_os("XDG_RUNTIME_DIR")?);
+    socket_in(&runtime_dir, ipc_client::current_uid()?)
+}
+
+fn socket_in(runtime_dir: &Path, uid: u32) -> Option<PathBuf> {
+    let metadata = fs::metadata(runtime_dir).ok()?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return None;
+    }
+    Some(runtime_dir.join("eww_sway_ipc_backend.sock"))
+}
+
+// The daemon socket if a daemon is accepting connections on it, a crashed daemon leaves the
+// file behind. Only used on startup, clients connect to the socket directly.
+pub fn running_daemon() -> Option<PathBuf> {
+    let path = daemon_socket_path()?;
+    UnixStream::connect(&path).ok().map(|_| path)
+}
+
+struct Subscriber {
+    // to disconnect it
+    stream: UnixStream,
+    events: Vec<u32>,
+    queue: SyncSender<IPCFormat>,
+}
+
+// What the daemon knows about sway, kept up to date from its events. Each field holds the reply
+// to the query as sway sent it, None until it is fetched or after an event made it stale.
+#[derive(Default)]
+struct SwayState {
+    workspaces: Option<String>,
+    outputs: Option<String>,
+    // the windows and the rest of the layout
+    tree: Option<String>,
+    // GET_BINDING_STATE, taken from the mode events without asking sway
+    mode: Option<String>,
+    version: Option<String>,
+    inputs: Option<String>,
+    // bumped by every event, a reply that was fetched across one is not kept
+    generation: u64,
+}
+
+impl SwayState {
+    fn field(&mut self, payload_type: u32) -> Option<&mut Option<String>> {
+        let field = match payload_type {
+            t if t == IPCMessages::GetWorkspaces as u32 => &mut self.workspaces,
+            t if t == IPCMessages::GetOutputs as u32 => &mut self.outputs,
+            t if t == IPCMessages::GetTree as u32 => &mut self.tree,
+            t if t == IPCMessages::GetBindingState as u32 => &mut self.mode,
+            t if t == IPCMessages::GetVersion as u32 => &mut self.version,
+            t if t == IPCMessages::GetInputs as u32 => &mut self.inputs,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    // Forgets everything, for a new connection to sway
+    fn clear(&mut self) {
+        *self = SwayState {
+            generation: self.generation + 1,
+            ..SwayState::default()
+        };
+    }
+
+    // Updates the state from an event and returns the queries it made stale that are fetched
+    // again right away. The tree is large and changes with every window title, so it waits
+    // until a client asks for it.
+    fn apply(&mut self, event: &IPCFormat) -> Vec<IPCMessages> {
+        self.generation += 1;
+        match IPCEvents::from_u32(event.payload_type) {
+            Some(IPCEvents::Workspace) | Some(IPCEvents::Output) => {
+                self.workspaces = None;
+                self.outputs = None;
+                self.tree = None;
+                vec![IPCMessages::GetWorkspaces, IPCMessages::GetOutputs]
+            }
+            // urgency and the workspace representation follow the windows
+            Some(IPCEvents::Window) => {
+                self.workspaces = None;
+                self.tree = None;
+                vec![IPCMessages::GetWorkspaces]
+            }
+            Some(IPCEvents::Mode) => {
+                self.mode = ipc_types::from_json::<ModeEvent>(&event.payload)
+                    .ok()
+                    .map(|mode| format!("{{\"name\":\"{}\"}}", mode.change));
+                vec![]
+            }
+            Some(IPCEvents::Input) => {
+                self.inputs = None;
+                vec![IPCMessages::GetInputs]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+// Speaks i3-ipc to its clients so the listen modes work unchanged on top of it. Queries are
+// answered from the state or forwarded over a single connection to sway, and the events of a
+// single subscription are passed on to every client that subscribed to them. No lock is held
+// while waiting for sway, the client takes turns on the connection by itself.
+pub struct Daemon {
+    // None while sway cannot be reached
+    sway: Mutex<Option<Arc<IPCClient>>>,
+    state: Mutex<SwayState>,
+    subscribers: Mutex<Vec<Subscriber>>,
+    // connections being served
+    clients: AtomicUsize,
+    max_payload_len: u32,
+}
+
+// The read-only queries the modes make, anything else such as RUN_COMMAND is refused so the
+// daemon cannot be used to control sway
+const ALLOWED: [IPCMessages; 6] = [
+    IPCMessages::GetWorkspaces,
+    IPCMessages::GetOutputs,
+    IPCMessages::GetTree,
+    IPCMessages::GetVersion,
+    IPCMessages::GetBindingState,
+    IPCMessages::GetInputs,
+];
+
+// The reply to a refused message, in the shape sway uses for failures of that message
+fn refusal(request: &IPCFormat) -> IPCFormat {
+    let error = "{\"success\":false,\"error\":\"not allowed through the daemon\"}";
+    let payload = if request.payload_type == IPCMessages::RunCommand as u32 {
+        format!("[{error}]")
+    } else {
+        error.to_string()
+    };
+    IPCFormat::new(request.payload_type).payload(&payload)
+}
+
+// Every event the daemon subscribes to. Sway sends a tick to each new subscriber, which cannot
+// be relayed, so ticks are left out.
+fn relayed_events(wm: WindowManager) -> Vec<IPCEvents> {
+    IPCEvents::ALL
+        .into_iter()
+        .filter(|event| *event != IPCEvents::Tick && wm.supports_event(*event))
+        .collect()
+}
+
+// Why the events in a SUBSCRIBE payload cannot be relayed, if they cannot
+fn subscription_error(names: &[String], wm: WindowManager) -> Option<String> {
+    let relayed = relayed_events(wm);
+    names
+        .iter()
+        .find_map(|name| match IPCEvents::from_name(name) {
+            None => Some(format!("unknown event {name}")),
+            Some(event) if !relayed.contains(&event) => {
+                Some(format!("the daemon does not relay {name} events"))
+            }
+            Some(_) => None,
+        })
+}
+
+impl Daemon {
+    pub fn new(max_payload_len: u32) -> Daemon {
+        Daemon {
+            sway: Mutex::new(None),
+            state: Mutex::new(SwayState::default()),
+            subscribers: Mutex::new(vec![]),
+            clients: AtomicUsize::new(0),
+            max_payload_len,
+        }
+    }
+
+    // Starts forwarding queries to a freshly connected sway
+    pub fn attach(&self, sway: IPCClient) {
+        self.state.lock().unwrap().clear();
+        *self.sway.lock().unwrap() = Some(Arc::new(sway));
+    }
+
+    // Forgets sway and disconnects every subscriber so they reconnect once sway is back
+    pub fn detach(&self) {
+        *self.sway.lock().unwrap() = None;
+        self.state.lock().unwrap().clear();
+        for subscriber in self.subscribers.lock().unwrap().drain(..) {
+            let _ = subscriber.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    pub fn answer(&self, request: &IPCFormat) -> Result<IPCFormat, IPCError> {
+        if !ALLOWED
+            .iter()
+            .any(|message| *message as u32 == request.payload_type)
+        {
+            return Ok(refusal(request));
+        }
+        if request.payload.is_empty() {
+            let mut state = self.state.lock().unwrap();
+            if let Some(Some(payload)) = state.field(request.payload_type) {
+                return Ok(IPCFormat::new(request.payload_type).payload(payload));
+            }
+        }
+        self.fetch(request)
+    }
+
+    // Asks sway and keeps the reply in the state, unless an event arrived in the meantime
+    fn fetch(&self, request: &IPCFormat) -> Result<IPCFormat, IPCError> {
+        let sway = self
+            .sway
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or(IPCError::UnexpectedEof)?;
+        let generation = self.state.lock().unwrap().generation;
+        let reply = sway.forward(request)?;
+        if request.payload.is_empty() {
+            let mut state = self.state.lock().unwrap();
+            if state.generation == generation {
+                if let Some(field) = state.field(request.payload_type) {
+                    *field = Some(reply.payload.clone());
+                }
+            }
+        }
+        Ok(reply)
+    }
+
+    // Replies to a SUBSCRIBE and registers the client, under the same lock as publish so the
+    // reply always arrives before the first event
+    fn subscribe(&self, stream: &mut UnixStream, request: &IPCFormat) -> Result<(), IPCError> {
+        let names = ipc_types::strings_from_json(&request.payload)?;
+        let wm = self
+            .sway
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|sway| sway.window_manager());
+        let error = match wm {
+            Some(wm) => subscription_error(&names, wm),
+            None => Some(String::from("not connected to sway")),
+        };
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let reply = match &error {
+            Some(error) => format!("{{\"success\":false,\"error\":\"{error}\"}}"),
+            None => String::from("{\"success\":true}"),
+        };
+        ipc_client::send(
+            stream,
+            &IPCFormat::new(IPCMessages::Subscribe as u32).payload(&reply),
+        )?;
+        if error.is_none() {
+            let stream = stream.try_clone().map_err(IPCError::ConnectionError)?;
+            let mut writer = stream.try_clone().map_err(IPCError::ConnectionError)?;
+            let _ = writer.set_write_timeout(Some(SUBSCRIBER_WRITE_TIMEOUT));
+            let (queue, pending) = mpsc::sync_channel::<IPCFormat>(SUBSCRIBER_QUEUE_LEN);
+            // ends once the subscriber is dropped and its queue with it
+            thread::spawn(move || {
+                for event in pending {
+                    if ipc_client::send(&mut writer, &event).is_err() {
+                        let _ = writer.shutdown(Shutdown::Both);
+                        return;
+                    }
+                }
+            });
+            let events = names
+                .iter()
+                .filter_map(|name| IPCEvents::from_name(name))
+                .map(|event| event as u32)
+                .collect();
+            subscribers.push(Subscriber {
+                stream,
+                events,
+                queue,
+            });
+        }
+        Ok(())
+    }
+
+    // Updates the state from an event from sway and queues the event for its subscribers.
+    // Returns the queries to fetch again, which is left to the caller so the subscribers are
+    // not kept waiting.
+    pub(crate) fn publish(&self, event: &IPCFormat) -> Vec<IPCMessages> {
+        let stale = self.state.lock().unwrap().apply(event);
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            if !subscriber.events.contains(&event.payload_type) {
+                return true;
+            }
+            let queued = subscriber.queue.try_send(event.clone()).is_ok();
+            if !queued {
+                let _ = subscriber.stream.shutdown(Shutdown::Both);
+            }
+            queued
+        });
+        stale
+    }
+
+    // Fills the state in after connecting or an event, errors show up on the event connection
+    fn refresh(&self, messages: &[IPCMessages]) {
+        for message in messages {
+            let _ = self.fetch(&IPCFormat::new(*message as u32));
+        }
+    }
+
+    // Serves a client on a thread of its own, unless there are too many already
+    pub fn accept(self: &Arc<Self>, stream: UnixStream) -> bool {
+        if self.clients.fetch_add(1, Ordering::SeqCst) >= MAX_CLIENTS {
+            self.clients.fetch_sub(1, Ordering::SeqCst);
+            return false;
+        }
+        let daemon = Arc::clone(self);
+        thread::spawn(move || {
+            daemon.serve(stream);
+            daemon.clients.fetch_sub(1, Ordering::SeqCst);
+        });
+        true
+    }
+
+    // Answers a single client until it disconnects
+    pub fn serve(&self, mut stream: UnixStream) {
+        while let Ok(request) = ipc_client::recv_message(&mut stream, self.max_payload_len) {
+            let result = if request.payload_type == IPCMessages::Subscribe as u32 {
+                self.subscribe(&mut stream, &request)
+            } else {
+                self.answer(&request)
+                    .and_then(|reply| ipc_client::send(&mut stream, &reply))
+            };
+            // closing the connection tells the client to reconnect
+            if result.is_err() {
+                return;
+            }
+        }
+    }
+
+    // Connects to sway and relays its events until the connection drops or sway shuts down
+    fn relay(&self, options: &Options, connected: &mut bool) -> Result<(), IPCError> {
+        let (sway, path) = options.connect()?;
+        let wm = sway.window_manager();
+        let stream = Subscription::new()
+            .max_payload_len(options.max_payload_len)
+            .wm(wm)
+            .events(&relayed_events(wm))
+            .subscribe_on(ipc_client::connect_to(&path)?)?;
+        self.attach(sway);
+        *connected = true;
+        self.refresh(&[
+            IPCMessages::GetWorkspaces,
+            IPCMessages::GetOutputs,
+            IPCMessages::GetBindingState,
+            IPCMessages::GetVersion,
+        ]);
+        loop {
+            let event = stream.next_message()?;
+            let stale = self.publish(&event);
+            if event.payload_type == IPCEvents::Shutdown as u32 {
+                return Ok(());
+            }
+            self.refresh(&stale);
+        }
+    }
+}
+
+// Listens on the daemon socket and keeps a connection to sway, reconnecting like the listen
+// modes do until options.max_retries is exceeded
+pub fn run_daemon(options: &Options) -> Result<(), IPCError> {
+    let path = daemon_socket_path().ok_or(IPCError::RuntimeDirError)?;
+    if running_daemon().is_some() {
+        return Err(IPCError::DaemonRunningError(path));
+    }
+    let _ = fs::remove_file(&path);
+    let socket = UnixListener::bind(&path).map_err(IPCError::BindError)?;
+    if options.verbose {
+        eprintln!("daemon listening on {}", path.display());
+    }
+
+    let daemon = Arc::new(Daemon::new(options.max_payload_len));
+    let clients = Arc::clone(&daemon);
+    let verbose = options.verbose;
+    thread::spawn(move || {
+        for stream in socket.incoming().flatten() {
+            if !clients.accept(stream) && verbose {
+                eprintln!("closed a connection, {MAX_CLIENTS} clients are connected already");
+            }
+        }
+    });
+
+    let mut failures: u32 = 0;
+    loop {
+        let mut connected = false;
+        let result = daemon.relay(options, &mut connected);
+        daemon.detach();
+        if connected {
+            failures = 0;
+        }
+        if let Err(e) = result {
+            if let IPCError::UnsupportedError(_) = e {
+                return Err(e);
+            }
+            if !connected {
+                failures += 1;
+            }
+            if options.max_retries.is_some_and(|max| failures > max) {
+                let _ = fs::remove_file(&path);
+                return Err(e);
+            }
+        }
+        thread::sleep(listener::backoff(failures));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipc_types::fixtures;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn workspaces() -> String {
+        fixtures::workspaces_json(&[fixtures::workspace("1", true, false)])
+    }
+
+    // Answers every GET_WORKSPACES with workspaces() and counts how many it got
+    fn fake_sway() -> (IPCClient, thread::JoinHandle<usize>) {
+        let (client_fd, mut sway_fd) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || {
+            let mut requests = 0;
+            while let Ok(request) = ipc_client::recv_message(&mut sway_fd, 1 << 20) {
+                requests += 1;
+                let reply = IPCFormat::new(request.payload_type).payload(&workspaces());
+                ipc_client::send(&mut sway_fd, &reply).unwrap();
+            }
+            requests
+        });
+        (IPCClient::from_stream(client_fd), handle)
+    }
+
+    #[test]
+    fn socket_needs_a_private_runtime_dir() {
+        let runtime_dir =
+            env::temp_dir().join(format!("eww_sway_ipc_daemon.{}", std::process::id()));
+        fs::create_dir_all(&runtime_dir).unwrap();
+        let uid = ipc_client::current_uid().unwrap();
+
+        fs::set_permissions(&runtime_dir, fs::Permissions::from_mode(0o755)).unwrap();
+        let shared = socket_in(&runtime_dir, uid);
+        fs::set_permissions(&runtime_dir, fs::Permissions::from_mode(0o700)).unwrap();
+        let private = socket_in(&runtime_dir, uid);
+        let other_user = socket_in(&runtime_dir, uid + 1);
+        let missing = socket_in(&runtime_dir.join("missing"), uid);
+        fs::remove_dir_all(&runtime_dir).unwrap();
+
+        assert_eq!(shared, None);
+        assert_eq!(private, Some(runtime_dir.join("eww_sway_ipc_backend.sock")));
+        assert_eq!(other_user, None);
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn keeps_the_state_until_an_event_changes_it() {
+        let daemon = Daemon::new(1 << 20);
+        let (sway, handle) = fake_sway();
+        daemon.attach(sway);
+
+        let request = IPCFormat::new(IPCMessages::GetWorkspaces as u32);
+        assert_eq!(daemon.answer(&request).unwrap().payload, workspaces());
+        assert_eq!(daemon.answer(&request).unwrap().payload, workspaces());
+        // a mode change leaves the workspaces alone, a workspace event does not
+        daemon.publish(&IPCFormat::new(IPCEvents::Mode as u32).payload("{}"));
+        daemon.answer(&request).unwrap();
+        let stale = daemon.publish(&IPCFormat::new(IPCEvents::Workspace as u32).payload("{}"));
+        assert_eq!(
+            stale,
+            vec![IPCMessages::GetWorkspaces, IPCMessages::GetOutputs]
+        );
+        daemon.answer(&request).unwrap();
+        // the mode comes from the events alone
+        daemon.publish(
+            &IPCFormat::new(IPCEvents::Mode as u32)
+                .payload("{ \"change\": \"resize\", \"pango_markup\": false }"),
+        );
+        let binding_state = IPCFormat::new(IPCMessages::GetBindingState as u32);
+        assert_eq!(
+            daemon.answer(&binding_state).unwrap().payload,
+            "{\"name\":\"resize\"}"
+        );
+
+        // commands never reach sway
+        let command = IPCFormat::new(IPCMessages::RunCommand as u32).payload("exit");
+        assert_eq!(
+            daemon.answer(&command).unwrap().payload,
+            "[{\"success\":false,\"error\":\"not allowed through the daemon\"}]"
+        );
+        let marks = IPCFormat::new(IPCMessages::GetMarks as u32);
+        assert!(daemon
+            .answer(&marks)
+            .unwrap()
+            .payload
+            .contains("\"success\":false"));
+
+        daemon.detach();
+        assert_eq!(handle.join().unwrap(), 2);
+        assert!(matches!(
+            daemon.answer(&request),
+            Err(IPCError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn events_are_not_held_up_by_queries() {
+        let daemon = Arc::new(Daemon::new(1 << 20));
+        let (client_fd, mut sway_fd) = UnixStream::pair().unwrap();
+        daemon.attach(IPCClient::from_stream(client_fd));
+
+        let asking = Arc::clone(&daemon);
+        let query = thread::spawn(move || {
+            asking
+                .answer(&IPCFormat::new(IPCMessages::GetTree as u32))
+                .unwrap()
+        });
+        // sway has the request but has not answered yet
+        let request = ipc_client::recv_message(&mut sway_fd, 1 << 20).unwrap();
+        daemon.publish(&IPCFormat::new(IPCEvents::Window as u32).payload("{}"));
+        ipc_client::send(
+            &mut sway_fd,
+            &IPCFormat::new(request.payload_type).payload("{}"),
+        )
+        .unwrap();
+        assert_eq!(query.join().unwrap().payload, "{}");
+
+        // the reply may predate the event, so it is asked for again
+        let query = thread::spawn(move || {
+            daemon
+                .answer(&IPCFormat::new(IPCMessages::GetTree as u32))
+                .unwrap()
+        });
+        let request = ipc_client::recv_message(&mut sway_fd, 1 << 20).unwrap();
+        ipc_client::send(
+            &mut sway_fd,
+            &IPCFormat::new(request.payload_type).payload("{}"),
+        )
+        .unwrap();
+        query.join().unwrap();
+    }
+
+    #[test]
+    fn relays_events_to_subscribed_clients() {
+        let daemon = Arc::new(Daemon::new(1 << 20));
+        let (sway, _handle) = fake_sway();
+        daemon.attach(sway);
+
+        let (client_fd, daemon_fd) = UnixStream::pair().unwrap();
+        let server = Arc::clone(&daemon);
+        thread::spawn(move || server.serve(daemon_fd));
+        let events = Subscription::new()
+            .event(IPCEvents::Mode)
+            .subscribe_on(client_fd)
+            .unwrap();
+
+        // only events the client subscribed to are passed on
+        daemon.publish(&IPCFormat::new(IPCEvents::Window as u32).payload("{}"));
+        let mode = IPCFormat::new(IPCEvents::Mode as u32)
+            .payload("{ \"change\": \"resize\", \"pango_markup\": false }");
+        daemon.publish(&mode);
+        assert_eq!(events.next_message().unwrap(), mode);
+
+        // ticks cannot be relayed, the subscription fails instead of going quiet
+        let (client_fd, daemon_fd) = UnixStream::pair().unwrap();
+        let server = Arc::clone(&daemon);
+        thread::spawn(move || server.serve(daemon_fd));
+        assert!(matches!(
+            Subscription::new()
+                .event(IPCEvents::Mode)
+                .event(IPCEvents::Tick)
+                .subscribe_on(client_fd),
+            Err(IPCError::SubscribeError)
+        ));
+        assert_eq!(
+            subscription_error(&[String::from("input")], WindowManager::I3),
+            Some(String::from("the daemon does not relay input events"))
+        );
+        assert_eq!(
+            subscription_error(&[String::from("ticks")], WindowManager::Sway),
+            Some(String::from("unknown event ticks"))
+        );
+
+        daemon.detach();
+        assert!(matches!(
+            events.next_message(),
+            Err(IPCError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn a_stalled_subscriber_does_not_hold_up_the_others() {
+        let daemon = Arc::new(Daemon::new(1 << 20));
+        let (sway, _handle) = fake_sway();
+        daemon.attach(sway);
+        let subscribe = || {
+            let (client_fd, daemon_fd) = UnixStream::pair().unwrap();
+            assert!(daemon.accept(daemon_fd));
+            Subscription::new()
+                .event(IPCEvents::Window)
+                .subscribe_on(client_fd)
+                .unwrap()
+        };
+        let stalled = subscribe();
+        let reading = subscribe();
+        let reader = thread::spawn(move || {
+            for _ in 0..2 * SUBSCRIBER_QUEUE_LEN {
+                reading.next_message().unwrap();
+            }
+        });
+
+        // enough to fill the socket buffer and the queue of the one that does not read
+        let event = IPCFormat::new(IPCEvents::Window as u32).payload(&" ".repeat(1 << 16));
+        let started = std::time::Instant::now();
+        for _ in 0..2 * SUBSCRIBER_QUEUE_LEN {
+            daemon.publish(&event);
+            // as sway would, so the one that reads keeps up
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(started.elapsed() < SUBSCRIBER_WRITE_TIMEOUT);
+        assert_eq!(daemon.subscribers.lock().unwrap().len(), 1);
+        // every event reached the other one
+        reader.join().unwrap();
+        // while the stalled one was cut off after what its buffers held
+        let delivered = std::iter::from_fn(|| stalled.next_message().ok()).count();
+        assert!(delivered < 2 * SUBSCRIBER_QUEUE_LEN);
+    }
+
+    #[test]
+    fn clients_beyond_the_limit_are_turned_away() {
+        let daemon = Arc::new(Daemon::new(1 << 20));
+        let clients: Vec<UnixStream> = (0..MAX_CLIENTS)
+            .map(|_| {
+                let (client_fd, daemon_fd) = UnixStream::pair().unwrap();
+                assert!(daemon.accept(daemon_fd));
+                client_fd
+            })
+            .collect();
+        let (_client_fd, daemon_fd) = UnixStream::pair().unwrap();
+        assert!(!daemon.accept(daemon_fd));
+        drop(clients);
+    }
+}
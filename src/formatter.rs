@@ -0,0 +1,790 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::icons::Icons;
+use crate::ipc_client::WindowManager;
+use crate::ipc_types::{Input, Node, Rect, Workspace};
+use crate::template::{self, Template, TemplateError, Value, WorkspaceTemplate};
+use crate::{json_parser, state};
+
+// How the workspace list is printed
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkspaceFormat {
+    // ["1", "2focused"], kept as the default for existing configs
+    Names,
+    // [{"name", "label", "num", "focused", "visible", "urgent", "output", "exists", "apps",
+    // "icons"}]
+    Objects,
+    // --template
+    Template(WorkspaceTemplate),
+    // (box (button ...) ...) for eww's literal widget
+    Yuck(YuckFormat),
+}
+
+// The order workspaces are printed in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorkspaceSort {
+    // as GET_WORKSPACES returns them, grouped by output
+    Sway,
+    // numbered workspaces by number, then named ones by name
+    Num,
+    // natural order, so "10" comes after "9"
+    Name,
+    // by output name, then as Num
+    Output,
+}
+
+impl WorkspaceSort {
+    fn cmp(&self, a: &Workspace, b: &Workspace) -> Ordering {
+        match self {
+            WorkspaceSort::Sway => Ordering::Equal,
+            WorkspaceSort::Num => {
+                state::cmp_workspaces(a, b).then_with(|| natural_cmp(&a.name, &b.name))
+            }
+            WorkspaceSort::Name => natural_cmp(&a.name, &b.name),
+            WorkspaceSort::Output => {
+                natural_cmp(&a.output, &b.output).then_with(|| WorkspaceSort::Num.cmp(a, b))
+            }
+        }
+    }
+}
+
+// Compares runs of digits by their value and everything else character by character
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        let (x, y) = match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => (*x, *y),
+        };
+        let ordering = if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (x, y) = (take_digits(&mut a), take_digits(&mut b));
+            let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+            x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+        } else {
+            a.next();
+            b.next();
+            x.cmp(&y)
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+// Which workspaces are printed and how
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceView {
+    pub format: WorkspaceFormat,
+    // only the workspaces on this output
+    pub output: Option<String>,
+    // {"eDP-1": [...], ...} instead of a single list
+    pub per_output: bool,
+    // --persistent, listed even while they do not exist
+    pub persistent: Vec<Placeholder>,
+    pub sort: WorkspaceSort,
+    // print "3:web" as "web", it still sorts as 3
+    pub strip_number: bool,
+    // the windows of each workspace are only looked up when this is set
+    pub icons: Option<Icons>,
+}
+
+impl Default for WorkspaceView {
+    fn default() -> WorkspaceView {
+        WorkspaceView {
+            format: WorkspaceFormat::Names,
+            output: None,
+            per_output: false,
+            persistent: vec![],
+            sort: WorkspaceSort::Sway,
+            strip_number: false,
+            icons: None,
+        }
+    }
+}
+
+impl WorkspaceView {
+    // Once connected, so the default --onclick talks to the right window manager
+    pub fn set_wm(&mut self, wm: WindowManager) {
+        if let WorkspaceFormat::Yuck(yuck) = &mut self.format {
+            yuck.set_wm(wm);
+        }
+    }
+
+    pub fn render(&self, workspaces: &[Workspace]) -> String {
+        let mut workspaces: Vec<Workspace> = self
+            .with_placeholders(workspaces)
+            .into_iter()
+            .filter(|workspace| {
+                self.output
+                    .as_ref()
+                    .is_none_or(|output| workspace.output == *output)
+            })
+            .collect();
+        workspaces.sort_by(|a, b| self.sort.cmp(a, b));
+        for workspace in &mut workspaces {
+            if self.strip_number {
+                workspace.label = strip_number(&workspace.name).to_string();
+            }
+            if let Some(icons) = &self.icons {
+                workspace.icons = workspace
+                    .apps
+                    .iter()
+                    .filter_map(|app| icons.icon(app))
+                    .map(String::from)
+                    .collect();
+            }
+        }
+        if self.per_output {
+            self.render_per_output(&workspaces)
+        } else {
+            workspace_list(&self.format, &workspaces)
+        }
+    }
+
+    // Adds the placeholders that have no workspace, sorted in with the workspaces of their
+    // output. Placeholders for outputs that are not connected are left out.
+    fn with_placeholders(&self, workspaces: &[Workspace]) -> Vec<Workspace> {
+        let mut all = workspaces.to_vec();
+        if self.persistent.is_empty() {
+            return all;
+        }
+        let outputs = outputs(workspaces);
+        // where sway would create the workspace when switching to it
+        let focused_output = workspaces
+            .iter()
+            .find(|workspace| workspace.focused)
+            .map(|workspace| workspace.output.as_str())
+            .unwrap_or_default();
+        for placeholder in &self.persistent {
+            let output = placeholder.output.as_deref().unwrap_or(focused_output);
+            if outputs.contains(&output) && !placeholder.exists_in(&all) {
+                all.push(placeholder.workspace(output));
+            }
+        }
+        let position = |output: &str| outputs.iter().position(|other| *other == output);
+        all.sort_by(|a, b| {
+            position(&a.output)
+                .cmp(&position(&b.output))
+                .then_with(|| state::cmp_workspaces(a, b))
+        });
+        all
+    }
+
+    fn render_per_output(&self, workspaces: &[Workspace]) -> String {
+        let entries: Vec<String> = outputs(workspaces)
+            .iter()
+            .map(|output| {
+                let on_output: Vec<Workspace> = workspaces
+                    .iter()
+                    .filter(|workspace| workspace.output == *output)
+                    .cloned()
+                    .collect();
+                let list = workspace_list(&self.format, &on_output);
+                // the template formats are not json themselves
+                let list = match self.format {
+                    WorkspaceFormat::Names | WorkspaceFormat::Objects => list,
+                    WorkspaceFormat::Template(_) | WorkspaceFormat::Yuck(_) => json_quote(&list),
+                };
+                format!("\"{output}\":{list}")
+            })
+            .collect();
+        format!("{{{}}}", entries.join(","))
+    }
+}
+
+// "3:web" and "3: web" become "web", names that are only a number are kept
+fn strip_number(name: &str) -> &str {
+    let rest = name.trim_start_matches(|c: char| c.is_ascii_digit());
+    match rest.strip_prefix(':') {
+        Some(label) if rest.len() < name.len() && !label.trim_start().is_empty() => {
+            label.trim_start()
+        }
+        _ => name,
+    }
+}
+
+// The outputs in the order sway lists their workspaces in
+fn outputs(workspaces: &[Workspace]) -> Vec<&str> {
+    let mut outputs: Vec<&str> = vec![];
+    for workspace in workspaces {
+        if !outputs.contains(&workspace.output.as_str()) {
+            outputs.push(&workspace.output);
+        }
+    }
+    outputs
+}
+
+// A workspace given to --persistent
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placeholder {
+    // as given, sway's names are compared after decoding them as it escapes more than escape does
+    pub name: String,
+    // None lists it on the focused output
+    pub output: Option<String>,
+}
+
+impl Placeholder {
+    // "1-5,mail", optionally with an output for each entry as in "1-5@eDP-1,6-9@HDMI-A-1".
+    // None for empty entries and backwards ranges.
+    pub fn parse_list(list: &str) -> Option<Vec<Placeholder>> {
+        let mut placeholders = vec![];
+        for entry in list.split(',') {
+            let (names, output) = match entry.rsplit_once('@') {
+                Some((names, output)) => (names, Some(output.to_string())),
+                None => (entry, None),
+            };
+            if names.is_empty() || output.as_deref() == Some("") {
+                return None;
+            }
+            let range = names
+                .split_once('-')
+                .and_then(|(first, last)| Some((first.parse().ok()?, last.parse().ok()?)));
+            match range {
+                Some((first, last)) if first <= last => {
+                    placeholders.extend((first..=last).map(|num: u32| Placeholder {
+                        name: num.to_string(),
+                        output: output.clone(),
+                    }))
+                }
+                Some(_) => return None,
+                None => placeholders.push(Placeholder {
+                    name: names.to_string(),
+                    output,
+                }),
+            }
+        }
+        Some(placeholders)
+    }
+
+    // Like sway, the number a name starts with or -1
+    fn num(&self) -> i64 {
+        let digits: String = self.name.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse().unwrap_or(-1)
+    }
+
+    // A numbered placeholder is taken by any workspace with its number, eg. 3 by "3:web"
+    fn exists_in(&self, workspaces: &[Workspace]) -> bool {
+        let num = self.num();
+        workspaces.iter().any(|workspace| {
+            if num >= 0 {
+                workspace.num == num
+            } else {
+                json_parser::unescape(&workspace.name) == self.name
+            }
+        })
+    }
+
+    fn workspace(&self, output: &str) -> Workspace {
+        let name = json_parser::escape(&self.name);
+        Workspace {
+            // sway's ids start at 1
+            id: 0,
+            num: self.num(),
+            name: name.clone(),
+            label: name,
+            output: output.to_string(),
+            focused: false,
+            visible: false,
+            urgent: false,
+            rect: Rect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            },
+            representation: None,
+            focus: vec![],
+            floating_nodes: 0,
+            exists: false,
+            apps: vec![],
+            icons: vec![],
+        }
+    }
+}
+
+pub fn workspace_list(format: &WorkspaceFormat, workspaces: &[Workspace]) -> String {
+    match format {
+        WorkspaceFormat::Names => workspace_names(workspaces),
+        WorkspaceFormat::Objects => workspace_objects(workspaces),
+        WorkspaceFormat::Template(template) => template.render(workspaces),
+        WorkspaceFormat::Yuck(yuck) => yuck.render(workspaces),
+    }
+}
+
+// EWW can use json lists ["a", "b", ... ], the focused workspace gets "focused" appended to its
+// name. Kept as it always was for existing configs, urgency is only in the other formats
+pub fn workspace_names(workspaces: &[Workspace]) -> String {
+    let names: Vec<String> = workspaces
+        .iter()
+        .map(|workspace| {
+            let mut name = workspace.label.clone();
+            if workspace.focused {
+                name.push_str("focused");
+            }
+            format!("\"{name}\"")
+        })
+        .collect();
+    format!("[{}]", names.join(","))
+}
+
+// One object per workspace, so names ending in "focused" cannot be mistaken for the flag
+pub fn workspace_objects(workspaces: &[Workspace]) -> String {
+    let objects: Vec<String> = workspaces
+        .iter()
+        .map(|workspace| {
+            // decoded when the table was loaded, unlike the fields that come from sway
+            let icons: Vec<String> = workspace
+                .icons
+                .iter()
+                .map(|icon| json_parser::escape(icon))
+                .collect();
+            format!(
+                "{{\"name\":\"{}\",\"label\":\"{}\",\"num\":{},\"focused\":{},\"visible\":{},\"urgent\":{},\"output\":\"{}\",\"exists\":{},\"apps\":[{}],\"icons\":[{}]}}",
+                workspace.name,
+                workspace.label,
+                workspace.num,
+                workspace.focused,
+                workspace.visible,
+                workspace.urgent,
+                workspace.output,
+                workspace.exists,
+                quoted_list(&workspace.apps),
+                quoted_list(&icons)
+            )
+        })
+        .collect();
+    format!("[{}]", objects.join(","))
+}
+
+pub const DEFAULT_ONCLICK: &str = "swaymsg workspace {name}";
+pub const I3_DEFAULT_ONCLICK: &str = "i3-msg workspace {name}";
+
+// A button per workspace with a class for each flag that is set. Everything is decoded from
+// json and quoted for yuck, the onclick values are shell quoted first as eww runs it with sh -c
+#[derive(Debug, Clone, PartialEq)]
+pub struct YuckFormat {
+    pub class: String,
+    pub focused_class: String,
+    pub visible_class: String,
+    pub urgent_class: String,
+    // for --persistent placeholders
+    pub empty_class: String,
+    onclick: Template,
+    // set by --onclick, which is kept whatever the window manager
+    custom_onclick: bool,
+}
+
+impl Default for YuckFormat {
+    fn default() -> YuckFormat {
+        YuckFormat {
+            class: String::from("ws"),
+            focused_class: String::from("focused"),
+            visible_class: String::from("visible"),
+            urgent_class: String::from("urgent"),
+            empty_class: String::from("empty"),
+            onclick: Template::parse(DEFAULT_ONCLICK, &template::WORKSPACE_FIELDS)
+                .unwrap()
+                .map_text(&yuck_escape),
+            custom_onclick: false,
+        }
+    }
+}
+
+impl YuckFormat {
+    // onclick is a template over the workspace fields, see template.rs
+    pub fn onclick(mut self, onclick: &str) -> Result<YuckFormat, TemplateError> {
+        self.onclick =
+            Template::parse(onclick, &template::WORKSPACE_FIELDS)?.map_text(&yuck_escape);
+        self.custom_onclick = true;
+        Ok(self)
+    }
+
+    pub fn set_wm(&mut self, wm: WindowManager) {
+        if self.custom_onclick {
+            return;
+        }
+        let onclick = match wm {
+            WindowManager::Sway => DEFAULT_ONCLICK,
+            WindowManager::I3 => I3_DEFAULT_ONCLICK,
+        };
+        self.onclick = Template::parse(onclick, &template::WORKSPACE_FIELDS)
+            .unwrap()
+            .map_text(&yuck_escape);
+    }
+
+    pub fn render(&self, workspaces: &[Workspace]) -> String {
+        let buttons: Vec<String> = workspaces
+            .iter()
+            .map(|workspace| {
+                let mut classes = vec![self.class.as_str()];
+                for (set, class) in [
+                    (workspace.focused, &self.focused_class),
+                    (workspace.visible, &self.visible_class),
+                    (workspace.urgent, &self.urgent_class),
+                    (!workspace.exists, &self.empty_class),
+                ] {
+                    if set && !class.is_empty() {
+                        classes.push(class);
+                    }
+                }
+                let onclick = self
+                    .onclick
+                    .render(&|field| match template::workspace_field(workspace, field) {
+                        Value::Text(text) => Value::Text(yuck_escape(&shell_quote(&text))),
+                        value => value,
+                    });
+                // the icons follow the name, eg. "1  "
+                let mut text = json_parser::unescape(&workspace.label);
+                for icon in &workspace.icons {
+                    text.push(' ');
+                    text.push_str(icon);
+                }
+                format!(
+                    " (button :class \"{}\" :onclick \"{}\" \"{}\")",
+                    yuck_escape(classes.join(" ").trim()),
+                    onclick,
+                    yuck_escape(&text)
+                )
+            })
+            .collect();
+        format!("(box{})", buttons.concat())
+    }
+}
+
+// Newlines and tabs are written as escapes so each list stays on one line, other control
+// characters are dropped
+fn yuck_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Single quotes unless the text is safe as it is, so numbers and plain names stay readable
+fn shell_quote(text: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-+=.,:/@%".contains(c);
+    if !text.is_empty() && text.chars().all(safe) {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+// Strings that are already json escaped, as the items of a json list
+fn quoted_list(strings: &[String]) -> String {
+    let quoted: Vec<String> = strings.iter().map(|s| format!("\"{s}\"")).collect();
+    quoted.join(",")
+}
+
+// Quotes text that is not escaped yet as a json string
+fn json_quote(text: &str) -> String {
+    format!("\"{}\"", json_parser::escape(text))
+}
+
+// Strings coming out of json_parser keep their escapes, so they can be quoted as they are
+fn json_opt_string(value: Option<&str>) -> String {
+    match value {
+        Some(s) => format!("\"{s}\""),
+        None => String::from("null"),
+    }
+}
+
+// {"title", "app_id", "class", "pid", "floating", "fullscreen"} for the window, or {} when
+// nothing is focused
+pub fn window_json(window: Option<&Node>) -> String {
+    let window = match window {
+        Some(window) if window.is_window() => window,
+        _ => return String::from("{}"),
+    };
+    let class = window
+        .window_properties
+        .as_ref()
+        .and_then(|properties| properties.class.as_deref());
+    let pid = match window.pid {
+        Some(pid) => pid.to_string(),
+        None => String::from("null"),
+    };
+    format!(
+        "{{\"title\":{},\"app_id\":{},\"class\":{},\"pid\":{},\"floating\":{},\"fullscreen\":{}}}",
+        json_opt_string(window.name.as_deref()),
+        json_opt_string(window.app_id.as_deref()),
+        json_opt_string(class),
+        pid,
+        window.is_floating(),
+        window.is_fullscreen()
+    )
+}
+
+// {"name", "pango_markup"} for the active binding mode
+pub fn mode_json(name: &str, pango_markup: bool) -> String {
+    format!("{{\"name\":\"{name}\",\"pango_markup\":{pango_markup}}}")
+}
+
+// {"identifier", "layout", "index", "layouts"} for a keyboard, or {} when there is none
+pub fn keyboard_layout_json(keyboard: Option<&Input>) -> String {
+    let keyboard = match keyboard {
+        Some(keyboard) => keyboard,
+        None => return String::from("{}"),
+    };
+    let index = match keyboard.xkb_active_layout_index {
+        Some(index) => index.to_string(),
+        None => String::from("null"),
+    };
+    let layouts: Vec<String> = keyboard
+        .xkb_layout_names
+        .iter()
+        .map(|layout| format!("\"{layout}\""))
+        .collect();
+    format!(
+        "{{\"identifier\":\"{}\",\"layout\":{},\"index\":{},\"layouts\":[{}]}}",
+        keyboard.identifier,
+        json_opt_string(keyboard.xkb_active_layout_name.as_deref()),
+        index,
+        layouts.join(",")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipc_types::fixtures::workspace;
+
+    #[test]
+    fn workspace_names_marks_focused() {
+        let workspaces = vec![
+            workspace("1", false, false),
+            workspace("2", true, false),
+            workspace("3", false, true),
+            workspace("4", true, true),
+        ];
+        // urgency would break configs matching on the "focused" suffix
+        assert_eq!(
+            workspace_names(&workspaces),
+            "[\"1\",\"2focused\",\"3\",\"4focused\"]"
+        );
+        assert_eq!(workspace_names(&[]), "[]");
+    }
+
+    #[test]
+    fn workspace_objects_keep_flags_separate() {
+        let workspaces = vec![
+            workspace("3focused", false, false),
+            workspace("4", true, true),
+        ];
+        assert_eq!(
+            workspace_list(&WorkspaceFormat::Objects, &workspaces),
+            "[{\"name\":\"3focused\",\"label\":\"3focused\",\"num\":3,\"focused\":false,\"visible\":false,\"urgent\":false,\"output\":\"eDP-1\",\"exists\":true,\"apps\":[],\"icons\":[]},{\"name\":\"4\",\"label\":\"4\",\"num\":4,\"focused\":true,\"visible\":true,\"urgent\":true,\"output\":\"eDP-1\",\"exists\":true,\"apps\":[],\"icons\":[]}]"
+        );
+        assert_eq!(workspace_objects(&[]), "[]");
+    }
+
+    #[test]
+    fn yuck_buttons_get_classes_and_onclick() {
+        let workspaces = vec![workspace("1", true, false), workspace("mail", false, true)];
+        assert_eq!(
+            workspace_list(&WorkspaceFormat::Yuck(YuckFormat::default()), &workspaces),
+            "(box (button :class \"ws focused visible\" :onclick \"swaymsg workspace 1\" \"1\") (button :class \"ws urgent\" :onclick \"swaymsg workspace mail\" \"mail\"))"
+        );
+
+        // literal quotes are escaped, the values shell quoted and then escaped too
+        let mut yuck = YuckFormat::default()
+            .onclick("notify-send \"{name}\"{?urgent: && urgent}")
+            .unwrap();
+        yuck.class = String::from("w\"s");
+        yuck.visible_class = String::new();
+        assert_eq!(
+            yuck.render(&[workspace("a\\\"b", true, true)]),
+            "(box (button :class \"w\\\"s focused urgent\" :onclick \"notify-send \\\"'a\\\"b'\\\" && urgent\" \"a\\\"b\"))"
+        );
+        let workspaces = vec![
+            workspace("web\\/dev", false, false),
+            workspace("it's", false, false),
+            workspace("say \\\"hi\\\" \\\\o/", false, false),
+        ];
+        assert_eq!(
+            YuckFormat::default().render(&workspaces),
+            "(box (button :class \"ws\" :onclick \"swaymsg workspace web/dev\" \"web/dev\") (button :class \"ws\" :onclick \"swaymsg workspace 'it'\\\\''s'\" \"it's\") (button :class \"ws\" :onclick \"swaymsg workspace 'say \\\"hi\\\" \\\\o/'\" \"say \\\"hi\\\" \\\\o/\"))"
+        );
+        assert_eq!(yuck.render(&[]), "(box)");
+        // control characters would break the line
+        assert_eq!(
+            YuckFormat::default().render(&[workspace("one\\ttwo\\r\\n\\u0007", false, false)]),
+            "(box (button :class \"ws\" :onclick \"swaymsg workspace 'one\\ttwo\\r\\n'\" \"one\\ttwo\\r\\n\"))"
+        );
+
+        // i3 gets i3-msg unless the command was given
+        let mut view = WorkspaceView {
+            format: WorkspaceFormat::Yuck(YuckFormat::default()),
+            ..WorkspaceView::default()
+        };
+        view.set_wm(WindowManager::I3);
+        assert_eq!(
+            view.render(&[workspace("1", false, false)]),
+            "(box (button :class \"ws\" :onclick \"i3-msg workspace 1\" \"1\"))"
+        );
+        yuck.set_wm(WindowManager::I3);
+        assert!(yuck
+            .render(&[workspace("1", false, false)])
+            .contains(":onclick \"notify-send"));
+    }
+
+    #[test]
+    fn workspaces_are_filtered_and_grouped_by_output() {
+        let mut external = workspace("9", false, false);
+        external.output = String::from("HDMI-A-1");
+        let workspaces = vec![
+            workspace("1", true, false),
+            workspace("2", false, false),
+            external,
+        ];
+        let mut view = WorkspaceView {
+            output: Some(String::from("HDMI-A-1")),
+            ..WorkspaceView::default()
+        };
+        assert_eq!(view.render(&workspaces), "[\"9\"]");
+
+        view.output = None;
+        view.per_output = true;
+        assert_eq!(
+            view.render(&workspaces),
+            "{\"eDP-1\":[\"1focused\",\"2\"],\"HDMI-A-1\":[\"9\"]}"
+        );
+
+        // other formats are quoted as strings
+        view.format = WorkspaceFormat::Yuck(YuckFormat::default().onclick("").unwrap());
+        view.output = Some(String::from("HDMI-A-1"));
+        assert_eq!(
+            view.render(&workspaces),
+            "{\"HDMI-A-1\":\"(box (button :class \\\"ws\\\" :onclick \\\"\\\" \\\"9\\\"))\"}"
+        );
+        assert_eq!(view.render(&[]), "{}");
+    }
+
+    #[test]
+    fn placeholders_are_sorted_in_with_their_output() {
+        let mut external = workspace("9", false, false);
+        external.output = String::from("HDMI-A-1");
+        let workspaces = vec![
+            workspace("1", true, false),
+            workspace("3:web", false, false),
+            // sway escapes the / in its json
+            workspace("web\\/dev", false, false),
+            external,
+        ];
+
+        let mut persistent = Placeholder::parse_list("1-4,mail,web/dev,say \"hi\"").unwrap();
+        persistent.extend(Placeholder::parse_list("6@HDMI-A-1,7@DP-2").unwrap());
+        let view = WorkspaceView {
+            format: WorkspaceFormat::Template(
+                WorkspaceTemplate::new("{name}{?exists:|?}", ",", "{items}").unwrap(),
+            ),
+            persistent,
+            ..WorkspaceView::default()
+        };
+        // 1 and 3 exist, DP-2 is not connected
+        assert_eq!(
+            view.render(&workspaces),
+            "1,2?,3:web,4?,web/dev,mail?,say \"hi\"?,6?,9"
+        );
+
+        assert_eq!(Placeholder::parse_list("1,,2"), None);
+        assert_eq!(Placeholder::parse_list("1@"), None);
+    }
+
+    #[test]
+    fn workspaces_are_sorted_and_stripped() {
+        let workspace = |name: &str, output: &str| Workspace {
+            output: output.to_string(),
+            ..workspace(name, false, false)
+        };
+        let workspaces = vec![
+            workspace("3:web", "eDP-1"),
+            workspace("10", "eDP-1"),
+            workspace("mail", "eDP-1"),
+            workspace("2", "HDMI-A-1"),
+            workspace("#irc", "HDMI-A-1"),
+        ];
+        let labels = |sort: WorkspaceSort, strip_number: bool| {
+            let view = WorkspaceView {
+                format: WorkspaceFormat::Template(
+                    WorkspaceTemplate::new("{label}", ",", "{items}").unwrap(),
+                ),
+                sort,
+                strip_number,
+                ..WorkspaceView::default()
+            };
+            view.render(&workspaces)
+        };
+        assert_eq!(labels(WorkspaceSort::Sway, false), "3:web,10,mail,2,#irc");
+        assert_eq!(labels(WorkspaceSort::Num, false), "2,3:web,10,#irc,mail");
+        assert_eq!(labels(WorkspaceSort::Name, false), "#irc,2,3:web,10,mail");
+        assert_eq!(labels(WorkspaceSort::Output, true), "2,#irc,web,10,mail");
+
+        assert_eq!(strip_number("3: web"), "web");
+        assert_eq!(strip_number("3:"), "3:");
+        assert_eq!(strip_number("web:3"), "web:3");
+        assert_eq!(natural_cmp("a2b", "a10a"), Ordering::Less);
+        assert_eq!(natural_cmp("07", "7"), Ordering::Equal);
+    }
+
+    #[test]
+    fn apps_are_mapped_to_icons() {
+        let mut first = workspace("1", true, false);
+        first.apps = vec![String::from("foot"), String::from("Thunderbird")];
+        let workspaces = vec![first, workspace("2", false, false)];
+        let icons =
+            crate::ipc_types::from_json("{ \"default\": \"?\", \"apps\": { \"foot\": \"T\" } }")
+                .unwrap();
+        let mut view = WorkspaceView {
+            format: WorkspaceFormat::Yuck(YuckFormat::default().onclick("").unwrap()),
+            icons: Some(icons),
+            ..WorkspaceView::default()
+        };
+        assert_eq!(
+            view.render(&workspaces),
+            "(box (button :class \"ws focused visible\" :onclick \"\" \"1 T ?\") (button :class \"ws\" :onclick \"\" \"2\"))"
+        );
+        view.format = WorkspaceFormat::Template(
+            WorkspaceTemplate::new("{name}:{icons}", ",", "{items}").unwrap(),
+        );
+        assert_eq!(view.render(&workspaces), "1:T ?,2:");
+        view.format = WorkspaceFormat::Objects;
+        assert!(view
+            .render(&workspaces)
+            .contains("\"apps\":[\"foot\",\"Thunderbird\"],\"icons\":[\"T\",\"?\"]"));
+
+        // glyphs are written as \u escapes in the table
+        let icons = crate::ipc_types::from_json(
+            "{ \"apps\": { \"foot\": \"\\uf120\", \"thunderbird\": \"\\\"m\\\"\" } }",
+        )
+        .unwrap();
+        view.icons = Some(icons);
+        assert!(view
+            .render(&workspaces)
+            .contains("\"icons\":[\"\u{f120}\",\"\\\"m\\\"\"]"));
+        view.format =
+            WorkspaceFormat::Template(WorkspaceTemplate::new("{icons}", "", "{items}").unwrap());
+        assert_eq!(view.render(&workspaces), "\u{f120} \"m\"");
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::ipc_types::{self, FromJsonObj};
+use crate::json_parser::{self, JsonError, JsonObj, JsonValue};
+
+// The --icons table from app ids (or X11 classes) to whatever the bar shows for them, a glyph
+// or the path of an icon file:
+//   {"default": "?", "apps": {"firefox": "", "foot": ""}}
+// Both keys are optional. Glyphs are often written as escapes like "\uf269", so the strings are
+// decoded here and each output format quotes them as it needs.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Icons {
+    // keyed by the lowercased app id
+    apps: HashMap<String, String>,
+    // for apps missing from the table, None leaves them out
+    default: Option<String>,
+}
+
+impl FromJsonObj for Icons {
+    fn from_json_obj(obj: &JsonObj) -> Result<Icons, JsonError> {
+        let mut apps = HashMap::new();
+        if let Some(table) = json_parser::get_opt_obj(obj, "apps")? {
+            for pair in table {
+                match &pair.value {
+                    JsonValue::String(icon) => {
+                        apps.insert(
+                            json_parser::unescape(&pair.key).to_lowercase(),
+                            json_parser::unescape(icon),
+                        );
+                    }
+                    _ => return Err(JsonError::KeyTypeError(pair.key.clone())),
+                }
+            }
+        }
+        Ok(Icons {
+            apps,
+            default: json_parser::get_opt_string(obj, "default")?
+                .map(|icon| json_parser::unescape(&icon)),
+        })
+    }
+}
+
+impl Icons {
+    pub fn load(path: &Path) -> Result<Icons, String> {
+        let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ipc_types::from_json(&json).map_err(|e| e.to_string())
+    }
+
+    // app ids are matched ignoring case, X11 classes are usually capitalised. They come from sway
+    // still escaped, the keys of the table are not
+    pub fn icon(&self, app: &str) -> Option<&str> {
+        self.apps
+            .get(&json_parser::unescape(app).to_lowercase())
+            .or(self.default.as_ref())
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn looks_up_apps_ignoring_case() {
+        let icons: Icons = ipc_types::from_json(
+            "{ \"default\": \"?\", \"apps\": { \"firefox\": \"F\", \"Foot\": \"/usr/share/icons/foot.svg\" } }",
+        )
+        .unwrap();
+        assert_eq!(icons.icon("Firefox"), Some("F"));
+        assert_eq!(icons.icon("foot"), Some("/usr/share/icons/foot.svg"));
+        assert_eq!(icons.icon("thunderbird"), Some("?"));
+
+        let icons: Icons = ipc_types::from_json(
+            "{ \"default\": \"\\uf059\", \"apps\": { \"firefox\": \"\\uf269\" } }",
+        )
+        .unwrap();
+        assert_eq!(icons.icon("firefox"), Some("\u{f269}"));
+        assert_eq!(icons.icon("foot"), Some("\u{f059}"));
+
+        let icons: Icons = ipc_types::from_json("{ \"apps\": { } }").unwrap();
+        assert_eq!(icons.icon("thunderbird"), None);
+        assert!(matches!(
+            ipc_types::from_json::<Icons>("{ \"apps\": { \"foot\": 1 } }"),
+            Err(JsonError::KeyTypeError(_))
+        ));
+    }
+}
@@ -1,15 +1,22 @@
-use std::cell::RefCell;
+use std::error::Error;
 use std::io::{Read, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
 use std::os::unix::net::UnixStream;
-use std::rc::Rc;
-use std::sync::{Arc, Mutex, mpsc};
-use std::thread;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::{env, io};
+use std::{fmt, fs};
 
-use crate::json_parser;
+use crate::cli::ArgError;
+use crate::ipc_types::{
+    self, BarConfig, BindingState, CommandResult, Config, Event, Input, Node, Output, Seat,
+    Success, Version, Workspace,
+};
+use crate::{formatter, json_parser, state};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u32)]
-enum IPCMessages {
+pub(crate) enum IPCMessages {
     RunCommand = 0u32,
     GetWorkspaces = 1u32,
     Subscribe = 2u32,
@@ -27,9 +34,10 @@ enum IPCMessages {
     GetSeats = 101u32,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u32)]
-enum IPCEvents {
-    Workspace = (1u32 << 31) | 0,
+pub enum IPCEvents {
+    Workspace = (1u32 << 31),
     Output = (1u32 << 31) | 1,
     Mode = (1u32 << 31) | 2,
     Window = (1u32 << 31) | 3,
@@ -41,223 +49,1037 @@ enum IPCEvents {
     Input = (1u32 << 31) | 0x15,
 }
 
+impl IPCEvents {
+    pub const ALL: [IPCEvents; 10] = [
+        IPCEvents::Workspace,
+        IPCEvents::Output,
+        IPCEvents::Mode,
+        IPCEvents::Window,
+        IPCEvents::BarConfigUpdate,
+        IPCEvents::Binding,
+        IPCEvents::Shutdown,
+        IPCEvents::Tick,
+        IPCEvents::BarStateUpdate,
+        IPCEvents::Input,
+    ];
+
+    // The name used for this event in a SUBSCRIBE payload
+    pub fn name(&self) -> &'static str {
+        match self {
+            IPCEvents::Workspace => "workspace",
+            IPCEvents::Output => "output",
+            IPCEvents::Mode => "mode",
+            IPCEvents::Window => "window",
+            IPCEvents::BarConfigUpdate => "barconfig_update",
+            IPCEvents::Binding => "binding",
+            IPCEvents::Shutdown => "shutdown",
+            IPCEvents::Tick => "tick",
+            IPCEvents::BarStateUpdate => "bar_state_update",
+            IPCEvents::Input => "input",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<IPCEvents> {
+        IPCEvents::ALL
+            .into_iter()
+            .find(|event| event.name() == name)
+    }
+
+    // Maps the type field of an event header back to the event
+    pub fn from_u32(payload_type: u32) -> Option<IPCEvents> {
+        IPCEvents::ALL
+            .into_iter()
+            .find(|event| *event as u32 == payload_type)
+    }
+}
+
+// i3 speaks the same protocol but lacks the sway only inputs, seats and some events
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowManager {
+    Sway,
+    I3,
+}
+
+impl WindowManager {
+    // sway and its forks send a variant in GET_VERSION, i3 does not
+    pub fn from_version(version: &Version) -> WindowManager {
+        match version.variant {
+            Some(_) => WindowManager::Sway,
+            None => WindowManager::I3,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowManager::Sway => "sway",
+            WindowManager::I3 => "i3",
+        }
+    }
+
+    pub fn supports_event(&self, event: IPCEvents) -> bool {
+        *self == WindowManager::Sway
+            || !matches!(event, IPCEvents::BarStateUpdate | IPCEvents::Input)
+    }
+}
+
 #[derive(Debug)]
 pub enum IPCError {
     ConnectionError(io::Error),
     PathNotFoundError,
     GeneralError,
     WriteError(io::Error),
+    ReadError(io::Error),
+    // sway closed the socket, possibly in the middle of a message
+    UnexpectedEof,
     ShutdownError(io::Error),
     JsonError(json_parser::JsonError),
+    SubscribeError,
+    UnknownEventError(u32),
+    ArgError(ArgError),
+    // the header did not start with "i3-ipc", the stream is out of sync
+    BadMagic([u8; 6]),
+    UnexpectedReplyType { expected: u32, got: u32 },
+    PayloadTooLarge { len: u32, max: u32 },
+    // a sway only request or event was used while talking to i3
+    UnsupportedError(&'static str),
+    BindError(io::Error),
+    DaemonRunningError(PathBuf),
+    // XDG_RUNTIME_DIR is unset or other users can get into it
+    RuntimeDirError,
+    // stdout was closed, eg. eww stopped listening
+    OutputError(io::Error),
+}
+
+impl fmt::Display for IPCError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IPCError::ConnectionError(e) => write!(f, "could not connect to sway: {e}"),
+            IPCError::PathNotFoundError => write!(
+                f,
+                "could not find the sway or i3 socket, set SWAYSOCK, I3SOCK or pass --socket"
+            ),
+            IPCError::GeneralError => write!(f, "something went wrong"),
+            IPCError::WriteError(e) => write!(f, "could not write to the sway socket: {e}"),
+            IPCError::ReadError(e) => write!(f, "could not read from the sway socket: {e}"),
+            IPCError::UnexpectedEof => write!(f, "sway closed the connection"),
+            IPCError::ShutdownError(e) => write!(f, "could not close the sway socket: {e}"),
+            IPCError::JsonError(e) => write!(f, "could not parse the reply from sway: {e}"),
+            IPCError::SubscribeError => write!(f, "sway refused the event subscription"),
+            IPCError::UnknownEventError(event_type) => {
+                write!(f, "sway sent an unknown event type {event_type:#x}")
+            }
+            IPCError::ArgError(e) => write!(f, "{e}"),
+            IPCError::BadMagic(magic) => write!(
+                f,
+                "sway sent a reply that does not start with \"{MAGIC_STR}\" but {magic:?}"
+            ),
+            IPCError::UnexpectedReplyType { expected, got } => write!(
+                f,
+                "sway replied with message type {got:#x} to a request of type {expected:#x}"
+            ),
+            IPCError::PayloadTooLarge { len, max } => write!(
+                f,
+                "sway sent a {len} byte reply which is more than the {max} byte limit"
+            ),
+            IPCError::UnsupportedError(what) => write!(f, "{what} is only supported by sway"),
+            IPCError::BindError(e) => write!(f, "could not listen on the daemon socket: {e}"),
+            IPCError::OutputError(e) => write!(f, "could not write to stdout: {e}"),
+            IPCError::DaemonRunningError(path) => {
+                write!(f, "a daemon is already listening on {}", path.display())
+            }
+            IPCError::RuntimeDirError => write!(
+                f,
+                "the daemon needs XDG_RUNTIME_DIR set to a directory only you can access"
+            ),
+        }
+    }
 }
 
+impl Error for IPCError {}
+
 impl From<json_parser::JsonError> for IPCError {
     fn from(e: json_parser::JsonError) -> IPCError {
-        return IPCError::JsonError(e);
+        IPCError::JsonError(e)
     }
 }
 
-struct IPCFormat {
-    payload_len: u32,
-    payload_type: u32,
-    payload: String,
+impl From<ArgError> for IPCError {
+    fn from(e: ArgError) -> IPCError {
+        IPCError::ArgError(e)
+    }
 }
 
-const MAGIC_STR: &str = "i3-ipc";
+fn read_error(e: io::Error) -> IPCError {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => IPCError::UnexpectedEof,
+        _ => IPCError::ReadError(e),
+    }
+}
 
-enum WorkspaceEventT {
-    Focused,
-    Initialized,
-    Empty,
+// A single i3-ipc message, the length in the header is always derived from the payload so it
+// cannot disagree with what is actually sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct IPCFormat {
+    pub payload_type: u32,
+    pub payload: String,
 }
 
-pub fn connect() -> Result<UnixStream, IPCError> {
-    match env::var_os("SWAYSOCK") {
-        Some(opt) => {
-            // connect
-            match UnixStream::connect(opt) {
-                Ok(fd) => return Ok(fd),
-                Err(e) => return Err(IPCError::ConnectionError(e)),
-            }
+const MAGIC_STR: &str = "i3-ipc";
+// magic, payload length and payload type
+pub const HEADER_LEN: usize = 14;
+
+// Replies larger than this are refused instead of allocated, a get_tree with hundreds of windows
+// is still only a few MiB
+pub const DEFAULT_MAX_PAYLOAD_LEN: u32 = 64 << 20;
+
+impl IPCFormat {
+    pub fn new(payload_type: u32) -> IPCFormat {
+        IPCFormat {
+            payload_type,
+            payload: String::new(),
         }
-        None => {
-            return Err(IPCError::PathNotFoundError);
+    }
+
+    pub fn payload(mut self, payload: &str) -> IPCFormat {
+        self.payload = payload.to_string();
+        self
+    }
+
+    pub fn payload_len(&self) -> u32 {
+        self.payload.len() as u32
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        bytes.extend_from_slice(MAGIC_STR.as_bytes());
+        bytes.extend_from_slice(&self.payload_len().to_ne_bytes());
+        bytes.extend_from_slice(&self.payload_type.to_ne_bytes());
+        bytes.extend_from_slice(self.payload.as_bytes());
+        bytes
+    }
+
+    // Validates a header and returns the payload length and type it announces
+    pub fn decode_header(
+        header: &[u8; HEADER_LEN],
+        max_payload_len: u32,
+    ) -> Result<(u32, u32), IPCError> {
+        if &header[..6] != MAGIC_STR.as_bytes() {
+            let mut magic = [0u8; 6];
+            magic.copy_from_slice(&header[..6]);
+            return Err(IPCError::BadMagic(magic));
+        }
+        let payload_len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+        let payload_type = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+        if payload_len > max_payload_len {
+            return Err(IPCError::PayloadTooLarge {
+                len: payload_len,
+                max: max_payload_len,
+            });
         }
+        Ok((payload_len, payload_type))
+    }
+
+    // Decodes the message at the start of bytes, returning it with the number of bytes it took
+    pub fn decode(bytes: &[u8], max_payload_len: u32) -> Result<(IPCFormat, usize), IPCError> {
+        let header = bytes
+            .get(..HEADER_LEN)
+            .and_then(|header| header.try_into().ok())
+            .ok_or(IPCError::UnexpectedEof)?;
+        let (payload_len, payload_type) = IPCFormat::decode_header(header, max_payload_len)?;
+        let end = HEADER_LEN + payload_len as usize;
+        let payload = bytes.get(HEADER_LEN..end).ok_or(IPCError::UnexpectedEof)?;
+        let message = IPCFormat {
+            payload_type,
+            payload: String::from_utf8_lossy(payload).into_owned(),
+        };
+        Ok((message, end))
     }
 }
 
-fn send_msg() -> io::Result<()> {
-    Ok(())
+pub fn connect() -> Result<UnixStream, IPCError> {
+    connect_to(&find_socket_path(None, None)?)
 }
 
-pub fn run_ipc() -> Result<(), IPCError> {
-    let (tx, rx) = mpsc::channel();
-    let (ws_tx, ws_rx) = mpsc::channel();
+pub fn connect_to(path: &Path) -> Result<UnixStream, IPCError> {
+    match UnixStream::connect(path) {
+        Ok(fd) => Ok(fd),
+        Err(e) => Err(IPCError::ConnectionError(e)),
+    }
+}
 
-    let workspace_msg = IPCFormat {
-        payload_len: 13,
-        payload_type: IPCMessages::Subscribe as u32,
-        payload: String::from("[\"workspace\"]"),
+// /proc/self belongs to the user the process runs as
+pub fn current_uid() -> Option<u32> {
+    fs::metadata("/proc/self")
+        .ok()
+        .map(|metadata| metadata.uid())
+}
+
+// Tries an explicit path, then SWAYSOCK and I3SOCK, and finally the sockets sway creates as
+// $XDG_RUNTIME_DIR/sway-ipc.$UID.$PID.sock and i3 as $XDG_RUNTIME_DIR/i3/ipc-socket.$PID.
+// SWAYSOCK is missing when eww is started from a systemd user unit for example. Only the
+// variables and sockets of wm are looked at when it is given.
+pub fn find_socket_path(
+    explicit: Option<&Path>,
+    wm: Option<WindowManager>,
+) -> Result<PathBuf, IPCError> {
+    if let Some(path) = explicit {
+        return Ok(path.to_path_buf());
+    }
+    // sway sets I3SOCK as well
+    let vars: &[&str] = match wm {
+        Some(WindowManager::I3) => &["I3SOCK"],
+        _ => &["SWAYSOCK", "I3SOCK"],
     };
+    let from_env: Vec<PathBuf> = vars
+        .iter()
+        .filter_map(env::var_os)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    let uid = current_uid().ok_or(IPCError::PathNotFoundError)?;
+    let runtime_dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(format!("/run/user/{uid}")),
+    };
+    find_socket(&from_env, &runtime_dir, uid, wm).ok_or(IPCError::PathNotFoundError)
+}
 
-    let event_listener_thread = thread::spawn(move || {
-        if let Ok(fd) = connect() {
-            let fd_mutex = Arc::new(Mutex::new(fd));
-            //println!("created new thread");
-            let fd_borrow = Arc::clone(&fd_mutex);
-            send(fd_borrow, &workspace_msg);
-            let fd_borrow_2 = Arc::clone(&fd_mutex);
-            if let Ok(json_string) = recv(fd_borrow_2) {
-                match json_string.as_str() {
-                    "{\"success\": true}" => {
-                        //println!("subscribed!");
-                        // TODO handle this error?
-                        listen(Arc::clone(&fd_mutex), &tx, &ws_rx);
-                    }
-                    "{\"success\": false}" => panic!("RUH ROH FAILED OT SUBSCRIPT"), // TODO handle
-                                                                                     // this better
-                    _ => { panic!("SHOULD NOT GET HERE");
-                                            }
-                }
+// A variable inherited from before sway restarted points at a socket nobody listens on any more,
+// so the sockets in runtime_dir are looked at unless one of the variables looks live. The first
+// variable is still returned if nothing else is found, connecting to it is retried.
+fn find_socket(
+    from_env: &[PathBuf],
+    runtime_dir: &Path,
+    uid: u32,
+    wm: Option<WindowManager>,
+) -> Option<PathBuf> {
+    if let Some(path) = from_env.iter().find(|path| is_live_socket(path)) {
+        return Some(path.clone());
+    }
+    let sway = || find_sway_socket(runtime_dir, uid);
+    let i3 = || find_i3_socket(&runtime_dir.join("i3"));
+    let found = match wm {
+        Some(WindowManager::Sway) => sway(),
+        Some(WindowManager::I3) => i3(),
+        None => sway().or_else(i3),
+    };
+    found.or_else(|| from_env.first().cloned())
+}
+
+// Whether path is a socket whose window manager is still running, judged by the pid in names
+// like sway-ipc.$UID.$PID.sock or ipc-socket.$PID without connecting to it. Sockets named any
+// other way only have to exist.
+fn is_live_socket(path: &Path) -> bool {
+    let is_socket = fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket());
+    let pid = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.strip_suffix(".sock").unwrap_or(name))
+        .and_then(|name| name.rsplit('.').next())
+        .and_then(|pid| pid.parse().ok());
+    is_socket && pid.is_none_or(is_alive)
+}
+
+fn is_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+fn find_sway_socket(runtime_dir: &Path, uid: u32) -> Option<PathBuf> {
+    find_live_socket(runtime_dir, &format!("sway-ipc.{uid}."), ".sock")
+}
+
+fn find_i3_socket(dir: &Path) -> Option<PathBuf> {
+    find_live_socket(dir, "ipc-socket.", "")
+}
+
+// Picks the newest $prefix$PID$suffix socket whose process is still alive, crashed window
+// managers leave theirs behind
+fn find_live_socket(dir: &Path, prefix: &str, suffix: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let pid: u32 = name
+                .strip_prefix(prefix)?
+                .strip_suffix(suffix)?
+                .parse()
+                .ok()?;
+            if !is_alive(pid) {
+                return None;
             }
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+// A connection to sway with one method per IPC message, each returning the typed reply
+pub struct IPCClient {
+    fd_mutex: Arc<Mutex<UnixStream>>,
+    max_payload_len: u32,
+    wm: WindowManager,
+}
+
+impl IPCClient {
+    pub fn connect() -> Result<IPCClient, IPCError> {
+        Ok(IPCClient::from_stream(connect()?))
+    }
+
+    pub fn connect_to(path: &Path) -> Result<IPCClient, IPCError> {
+        Ok(IPCClient::from_stream(connect_to(path)?))
+    }
+
+    pub fn from_stream(fd: UnixStream) -> IPCClient {
+        IPCClient {
+            fd_mutex: Arc::new(Mutex::new(fd)),
+            max_payload_len: DEFAULT_MAX_PAYLOAD_LEN,
+            wm: WindowManager::Sway,
         }
-    });
-
-    let workspace_status_thread = thread::spawn(move || {
-        if let Ok(fd) = connect() {
-            let fd_mutex = Arc::new(Mutex::new(fd));
-            loop {
-                let job = rx.recv().unwrap();
-                match job {
-                    IPCMessages::GetWorkspaces => {
-                            let result = get_workspaces_summary(Arc::clone(&fd_mutex));
-                            ws_tx.send(result).unwrap();
-                    }
-                    _ => continue,
-                }
-            }
+    }
+
+    pub fn max_payload_len(mut self, max_payload_len: u32) -> IPCClient {
+        self.max_payload_len = max_payload_len;
+        self
+    }
+
+    // Sway only requests and events are refused up front when talking to i3
+    pub fn wm(mut self, wm: WindowManager) -> IPCClient {
+        self.wm = wm;
+        self
+    }
+
+    pub fn window_manager(&self) -> WindowManager {
+        self.wm
+    }
+
+    pub fn detect_wm(&self) -> Result<WindowManager, IPCError> {
+        Ok(WindowManager::from_version(&self.get_version()?))
+    }
+
+    fn require_sway(&self, what: &'static str) -> Result<(), IPCError> {
+        match self.wm {
+            WindowManager::Sway => Ok(()),
+            WindowManager::I3 => Err(IPCError::UnsupportedError(what)),
         }
-        // TODO send and receive messages from event_listener_thread
-    });
+    }
 
+    fn request(&self, payload_type: IPCMessages, payload: &str) -> Result<String, IPCError> {
+        let message = IPCFormat::new(payload_type as u32).payload(payload);
+        Ok(self.forward(&message)?.payload)
+    }
 
-    workspace_status_thread.join().unwrap();
-    event_listener_thread.join().unwrap();
+    // Sends any message and returns the reply as is, the connection stays locked in between so
+    // replies cannot be mixed up between threads sharing the client
+    pub fn forward(&self, message: &IPCFormat) -> Result<IPCFormat, IPCError> {
+        let mut fd = self.fd_mutex.lock().unwrap();
+        send(&mut fd, message)?;
+        let reply = recv_message(&mut fd, self.max_payload_len)?;
+        // replies carry the type of the request they answer
+        if reply.payload_type != message.payload_type {
+            return Err(IPCError::UnexpectedReplyType {
+                expected: message.payload_type,
+                got: reply.payload_type,
+            });
+        }
+        Ok(reply)
+    }
+
+    // Runs each ; or , separated command in the payload, returning one result per command
+    pub fn run_command(&self, command: &str) -> Result<Vec<CommandResult>, IPCError> {
+        let reply = self.request(IPCMessages::RunCommand, command)?;
+        Ok(ipc_types::list_from_json(&reply)?)
+    }
+
+    pub fn get_workspaces(&self) -> Result<Vec<Workspace>, IPCError> {
+        let reply = self.request(IPCMessages::GetWorkspaces, "")?;
+        Ok(ipc_types::list_from_json(&reply)?)
+    }
+
+    // Events are only delivered on the subscribed connection, see Subscription for a way to
+    // actually read them
+    pub fn subscribe(&self, events: &[IPCEvents]) -> Result<Success, IPCError> {
+        if let Some(event) = events.iter().find(|event| !self.wm.supports_event(**event)) {
+            return Err(IPCError::UnsupportedError(event.name()));
+        }
+        let names: Vec<String> = events
+            .iter()
+            .map(|event| format!("\"{}\"", event.name()))
+            .collect();
+        let reply = self.request(IPCMessages::Subscribe, &format!("[{}]", names.join(",")))?;
+        Ok(ipc_types::from_json(&reply)?)
+    }
+
+    pub fn get_outputs(&self) -> Result<Vec<Output>, IPCError> {
+        let reply = self.request(IPCMessages::GetOutputs, "")?;
+        Ok(ipc_types::list_from_json(&reply)?)
+    }
+
+    pub fn get_tree(&self) -> Result<Node, IPCError> {
+        let reply = self.request(IPCMessages::GetTree, "")?;
+        Ok(ipc_types::from_json(&reply)?)
+    }
 
-    //println!("done");
-    Ok(())
+    pub fn get_marks(&self) -> Result<Vec<String>, IPCError> {
+        let reply = self.request(IPCMessages::GetMarks, "")?;
+        Ok(ipc_types::strings_from_json(&reply)?)
+    }
+
+    // GET_BAR_CONFIG without a payload lists the bar ids
+    pub fn get_bar_ids(&self) -> Result<Vec<String>, IPCError> {
+        let reply = self.request(IPCMessages::GetBarConfig, "")?;
+        Ok(ipc_types::strings_from_json(&reply)?)
+    }
+
+    pub fn get_bar_config(&self, id: &str) -> Result<BarConfig, IPCError> {
+        let reply = self.request(IPCMessages::GetBarConfig, id)?;
+        Ok(ipc_types::from_json(&reply)?)
+    }
+
+    pub fn get_version(&self) -> Result<Version, IPCError> {
+        let reply = self.request(IPCMessages::GetVersion, "")?;
+        Ok(ipc_types::from_json(&reply)?)
+    }
+
+    pub fn get_binding_modes(&self) -> Result<Vec<String>, IPCError> {
+        let reply = self.request(IPCMessages::GetBindingModes, "")?;
+        Ok(ipc_types::strings_from_json(&reply)?)
+    }
+
+    pub fn get_config(&self) -> Result<Config, IPCError> {
+        let reply = self.request(IPCMessages::GetConfig, "")?;
+        Ok(ipc_types::from_json(&reply)?)
+    }
+
+    pub fn send_tick(&self, payload: &str) -> Result<Success, IPCError> {
+        let reply = self.request(IPCMessages::SendTick, payload)?;
+        Ok(ipc_types::from_json(&reply)?)
+    }
+
+    // Only meaningful for i3, sway always replies with { "success": false }
+    pub fn sync(&self) -> Result<Success, IPCError> {
+        let reply = self.request(IPCMessages::Sync, "")?;
+        Ok(ipc_types::from_json(&reply)?)
+    }
+
+    pub fn get_binding_state(&self) -> Result<BindingState, IPCError> {
+        let reply = self.request(IPCMessages::GetBindingState, "")?;
+        Ok(ipc_types::from_json(&reply)?)
+    }
+
+    pub fn get_inputs(&self) -> Result<Vec<Input>, IPCError> {
+        self.require_sway("GET_INPUTS")?;
+        let reply = self.request(IPCMessages::GetInputs, "")?;
+        Ok(ipc_types::list_from_json(&reply)?)
+    }
+
+    pub fn get_seats(&self) -> Result<Vec<Seat>, IPCError> {
+        self.require_sway("GET_SEATS")?;
+        let reply = self.request(IPCMessages::GetSeats, "")?;
+        Ok(ipc_types::list_from_json(&reply)?)
+    }
 }
 
-// Should make this a "result"
-pub fn get_workspaces_summary(fd_mutex: Arc<Mutex<UnixStream>>) -> String {
-    let message = IPCFormat {
-        payload_len: 0,
-        payload_type: IPCMessages::GetWorkspaces as u32,
-        payload: String::from(""),
-    };
-    send(Arc::clone(&fd_mutex), &message);
-    let workspace_data = recv(Arc::clone(&fd_mutex)).unwrap();
-    // EWW can use json lists ["a", "b", ... ]
-    let mut result = String::from("[");
-    if let Ok(json_parser::JsonEntry::Array(workspace_json)) = json_parser::stojson(Rc::new(RefCell::new(workspace_data))) {
-        for workspace in workspace_json.iter() {
-            if let json_parser::JsonEntry::Object(workspace_obj) = workspace {
-                let mut ws_res = String::from("\"");
-                if let json_parser::JsonValue::String(name) = &workspace_obj[13].value {
-                    ws_res.push_str(&name);
-                }
-                if let json_parser::JsonValue::Boolean(focused) = workspace_obj[25].value {
-                    if focused {
-                        ws_res.push_str("focused");
-                    }
-                }
-                ws_res.push_str("\"");
-                result.push_str(&ws_res);
-                result.push(',');
-            }
+// Builds the set of events to subscribe to, eg.
+// Subscription::new().event(IPCEvents::Workspace).event(IPCEvents::Window).subscribe()
+#[derive(Debug)]
+pub struct Subscription {
+    events: Vec<IPCEvents>,
+    max_payload_len: u32,
+    wm: WindowManager,
+}
+
+impl Default for Subscription {
+    fn default() -> Subscription {
+        Subscription {
+            events: vec![],
+            max_payload_len: DEFAULT_MAX_PAYLOAD_LEN,
+            wm: WindowManager::Sway,
         }
-        result.pop();
-        result.push(']');
     }
-    result
 }
 
-fn send(fd: Arc<Mutex<UnixStream>>, message: &IPCFormat) -> Result<(), IPCError> {
-    let mut payload: Vec<u8> = message.payload.as_bytes().to_vec();
-    let mut header: Vec<u8> = MAGIC_STR.as_bytes().to_vec();
-    header.append(&mut message.payload_len.to_ne_bytes().to_vec());
-    header.append(&mut message.payload_type.to_ne_bytes().to_vec());
-    header.append(&mut payload);
+impl Subscription {
+    pub fn new() -> Subscription {
+        Subscription::default()
+    }
+
+    pub fn max_payload_len(mut self, max_payload_len: u32) -> Subscription {
+        self.max_payload_len = max_payload_len;
+        self
+    }
 
-    let mut fd = fd.lock().unwrap();
-    match fd.write_all(&header) {
-        Ok(_) => {} // TODO: should check the number of bytes written == to size of the message, if
-        // not throw a new error.
-        Err(e) => return Err(IPCError::WriteError(e)),
+    pub fn wm(mut self, wm: WindowManager) -> Subscription {
+        self.wm = wm;
+        self
+    }
+
+    pub fn event(mut self, event: IPCEvents) -> Subscription {
+        if !self.events.contains(&event) {
+            self.events.push(event);
+        }
+        self
+    }
+
+    pub fn events(self, events: &[IPCEvents]) -> Subscription {
+        events
+            .iter()
+            .fold(self, |subscription, event| subscription.event(*event))
+    }
+
+    // Subscribes on a fresh connection, which from then on only receives events
+    pub fn subscribe(self) -> Result<EventStream, IPCError> {
+        self.subscribe_on(connect()?)
+    }
+
+    pub fn subscribe_on(self, fd: UnixStream) -> Result<EventStream, IPCError> {
+        let client = IPCClient::from_stream(fd)
+            .max_payload_len(self.max_payload_len)
+            .wm(self.wm);
+        if !client.subscribe(&self.events)?.success {
+            return Err(IPCError::SubscribeError);
+        }
+        Ok(EventStream {
+            fd_mutex: client.fd_mutex,
+            max_payload_len: self.max_payload_len,
+        })
     }
-    Ok(())
 }
 
-fn recv(fd_mutex: Arc<Mutex<UnixStream>>) -> Result<String, IPCError> {
-    let mut fd = fd_mutex.lock().unwrap();
-    let mut buf_header = [0u8; 14];
-    fd.read_exact(&mut buf_header);
-    let payload_size: u32 =
-        u32::from_ne_bytes([buf_header[6], buf_header[7], buf_header[8], buf_header[9]]);
-    let mut payload = vec![0u8; payload_size as usize];
-    fd.read_exact(&mut payload);
-    Ok(String::from_utf8_lossy(&payload).into_owned())
+pub struct EventStream {
+    fd_mutex: Arc<Mutex<UnixStream>>,
+    max_payload_len: u32,
 }
 
-fn listen(fd_mutex: Arc<Mutex<UnixStream>>, tx: &mpsc::Sender<IPCMessages>, ws_rx: &mpsc::Receiver<String>) -> Result<(), IPCError> {
-    loop {
-        //println!("event triggered");
-        // TODO handle this error
-        if let Ok(json_event_string) = recv(Arc::clone(&fd_mutex)) {
-            ws_event_handler(Arc::clone(&fd_mutex), &json_event_string.as_str(), &tx, &ws_rx);
+impl EventStream {
+    // Blocks until sway sends the next event
+    pub fn next_event(&self) -> Result<Event, IPCError> {
+        let message = self.next_message()?;
+        match IPCEvents::from_u32(message.payload_type) {
+            Some(event_type) => Ok(Event::from_json(event_type, &message.payload)?),
+            None => Err(IPCError::UnknownEventError(message.payload_type)),
         }
     }
+
+    // The next event without decoding its payload
+    pub fn next_message(&self) -> Result<IPCFormat, IPCError> {
+        recv_message(&mut self.fd_mutex.lock().unwrap(), self.max_payload_len)
+    }
+
+    // A handle to the same connection, eg. to shut it down while another thread is blocked in
+    // next_event
+    pub fn try_clone_stream(&self) -> Result<UnixStream, IPCError> {
+        self.fd_mutex
+            .lock()
+            .unwrap()
+            .try_clone()
+            .map_err(IPCError::ConnectionError)
+    }
+}
+
+pub fn get_workspaces_summary(
+    client: &IPCClient,
+    view: &formatter::WorkspaceView,
+) -> Result<String, IPCError> {
+    let mut workspaces = client.get_workspaces()?;
+    if view.icons.is_some() {
+        state::fill_apps(&mut workspaces, &client.get_tree()?);
+    }
+    let mut view = view.clone();
+    view.set_wm(client.window_manager());
+    Ok(view.render(&workspaces))
+}
+
+pub fn send(fd: &mut UnixStream, message: &IPCFormat) -> Result<(), IPCError> {
+    // write_all keeps writing until the whole message is out or errors
+    fd.write_all(&message.encode())
+        .map_err(IPCError::WriteError)
+}
+
+pub fn recv_message(fd: &mut UnixStream, max_payload_len: u32) -> Result<IPCFormat, IPCError> {
+    let mut header = [0u8; HEADER_LEN];
+    fd.read_exact(&mut header).map_err(read_error)?;
+    let (payload_len, payload_type) = IPCFormat::decode_header(&header, max_payload_len)?;
+    let mut payload = vec![0u8; payload_len as usize];
+    fd.read_exact(&mut payload).map_err(read_error)?;
+    Ok(IPCFormat {
+        payload_type,
+        payload: String::from_utf8_lossy(&payload).into_owned(),
+    })
 }
 
-fn ws_event_handler(fd_mutex: Arc<Mutex<UnixStream>>, json_str: &str, tx: &mpsc::Sender<IPCMessages>, ws_rx: &mpsc::Receiver<String>) -> Result<(), IPCError> {
-    match client_state_mux(json_str) {
-        Ok(WorkspaceEventT::Focused) => {
-            ws_focus_handler(Arc::clone(&fd_mutex), &tx, &ws_rx);
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    fn read_request(sway_fd: &mut UnixStream) -> (u32, String) {
+        let mut header = [0u8; HEADER_LEN];
+        sway_fd.read_exact(&mut header).unwrap();
+        let (payload_len, payload_type) =
+            IPCFormat::decode_header(&header, DEFAULT_MAX_PAYLOAD_LEN).unwrap();
+        let mut payload = vec![0u8; payload_len as usize];
+        sway_fd.read_exact(&mut payload).unwrap();
+        (payload_type, String::from_utf8(payload).unwrap())
+    }
+
+    fn write_message(sway_fd: &mut UnixStream, payload_type: u32, payload: &str) {
+        let message = IPCFormat::new(payload_type).payload(payload);
+        sway_fd.write_all(&message.encode()).unwrap();
+    }
+
+    // Answers a single request on the other end of a socket pair the way sway would, handing
+    // back the request type and payload it received
+    fn fake_sway(reply: &'static str) -> (IPCClient, thread::JoinHandle<(u32, String)>) {
+        let (client_fd, mut sway_fd) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || {
+            let (payload_type, payload) = read_request(&mut sway_fd);
+            write_message(&mut sway_fd, payload_type, reply);
+            (payload_type, payload)
+        });
+        (IPCClient::from_stream(client_fd), handle)
+    }
+
+    #[test]
+    fn run_command_sends_payload() {
+        let (client, sway) =
+            fake_sway("[ { \"success\": true }, { \"success\": false, \"parse_error\": true, \"error\": \"Unknown command\" } ]");
+        let results = client.run_command("workspace 2; bogus").unwrap();
+        assert_eq!(
+            sway.join().unwrap(),
+            (0, String::from("workspace 2; bogus"))
+        );
+        assert_eq!(results.len(), 2);
+        assert!(results[0].success);
+        assert!(!results[1].success);
+        assert!(results[1].parse_error);
+        assert_eq!(results[1].error.as_deref(), Some("Unknown command"));
+    }
+
+    #[test]
+    fn get_marks_and_binding_modes_are_string_lists() {
+        let (client, sway) = fake_sway("[ \"mail\", \"music\" ]");
+        assert_eq!(client.get_marks().unwrap(), vec!["mail", "music"]);
+        assert_eq!(sway.join().unwrap().0, 5);
+
+        let (client, sway) = fake_sway("[ \"default\", \"resize\" ]");
+        assert_eq!(
+            client.get_binding_modes().unwrap(),
+            vec!["default", "resize"]
+        );
+        assert_eq!(sway.join().unwrap().0, 8);
+    }
+
+    #[test]
+    fn get_version_and_binding_state() {
+        let (client, sway) = fake_sway("{ \"human_readable\": \"1.9\", \"variant\": \"sway\", \"major\": 1, \"minor\": 9, \"patch\": 0, \"loaded_config_file_name\": \"/home/user/.config/sway/config\" }");
+        let version = client.get_version().unwrap();
+        assert_eq!(sway.join().unwrap().0, 7);
+        assert_eq!((version.major, version.minor, version.patch), (1, 9, 0));
+        assert_eq!(version.human_readable, "1.9");
+
+        let (client, sway) = fake_sway("{ \"name\": \"resize\" }");
+        assert_eq!(client.get_binding_state().unwrap().name, "resize");
+        assert_eq!(sway.join().unwrap().0, 12);
+    }
+
+    #[test]
+    fn get_tree_walks_nested_nodes() {
+        let (client, sway) = fake_sway("{ \"id\": 1, \"name\": \"root\", \"type\": \"root\", \"layout\": \"splith\", \"rect\": { \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }, \"urgent\": false, \"focused\": false, \"marks\": [ ], \"focus\": [ 3 ], \"nodes\": [ { \"id\": 3, \"name\": \"2\", \"type\": \"workspace\", \"layout\": \"splith\", \"rect\": { \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }, \"urgent\": false, \"focused\": false, \"marks\": [ ], \"focus\": [ 8 ], \"num\": 2, \"output\": \"eDP-1\", \"nodes\": [ { \"id\": 8, \"name\": \"~\", \"type\": \"con\", \"layout\": \"none\", \"rect\": { \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }, \"urgent\": false, \"focused\": true, \"marks\": [ \"term\" ], \"focus\": [ ], \"fullscreen_mode\": 0, \"app_id\": \"foot\", \"pid\": 1234, \"nodes\": [ ], \"floating_nodes\": [ ] } ], \"floating_nodes\": [ ] } ], \"floating_nodes\": [ ] }");
+        let tree = client.get_tree().unwrap();
+        assert_eq!(sway.join().unwrap().0, 4);
+        let workspace = &tree.nodes[0];
+        assert_eq!(workspace.node_type, "workspace");
+        assert_eq!(workspace.num, Some(2));
+        let window = &workspace.nodes[0];
+        assert!(window.focused);
+        assert_eq!(window.app_id.as_deref(), Some("foot"));
+        assert_eq!(window.pid, Some(1234));
+        assert_eq!(window.marks, vec!["term"]);
+        assert_eq!(window.window_properties, None);
+    }
+
+    #[test]
+    fn event_type_round_trips_through_header() {
+        for event in IPCEvents::ALL {
+            assert_eq!(IPCEvents::from_u32(event as u32), Some(event));
         }
-        Ok(WorkspaceEventT::Initialized) | Ok(WorkspaceEventT::Empty) => {
-            // TODO handle
-            if let Ok(next_json_str) = recv(Arc::clone(&fd_mutex)) {
-                ws_event_handler(Arc::clone(&fd_mutex), &next_json_str.as_str(), &tx, &ws_rx);
+        assert_eq!(IPCEvents::from_u32(IPCMessages::GetTree as u32), None);
+    }
+
+    #[test]
+    fn subscription_decodes_events_by_header_type() {
+        let (client_fd, mut sway_fd) = UnixStream::pair().unwrap();
+        let sway = thread::spawn(move || {
+            let request = read_request(&mut sway_fd);
+            write_message(&mut sway_fd, 2, "{ \"success\": true }");
+            write_message(
+                &mut sway_fd,
+                IPCEvents::Mode as u32,
+                "{ \"change\": \"resize\", \"pango_markup\": false }",
+            );
+            write_message(
+                &mut sway_fd,
+                IPCEvents::Tick as u32,
+                "{ \"first\": true, \"payload\": \"\" }",
+            );
+            write_message(&mut sway_fd, (1u32 << 31) | 0x40, "{ }");
+            request
+        });
+        let events = Subscription::new()
+            .event(IPCEvents::Mode)
+            .events(&[IPCEvents::Tick, IPCEvents::Mode])
+            .subscribe_on(client_fd)
+            .unwrap();
+        assert_eq!(
+            sway.join().unwrap(),
+            (2, String::from("[\"mode\",\"tick\"]"))
+        );
+        match events.next_event().unwrap() {
+            Event::Mode(mode) => {
+                assert_eq!(mode.change, "resize");
+                assert!(!mode.pango_markup);
             }
+            e => panic!("expected a mode event, got {e:?}"),
         }
-        Err(e) => return Err(e),
+        assert!(matches!(events.next_event().unwrap(), Event::Tick(tick) if tick.first));
+        assert!(matches!(
+            events.next_event().unwrap_err(),
+            IPCError::UnknownEventError(0x80000040)
+        ));
     }
 
-    Ok(())
-}
+    #[test]
+    fn failed_subscription_is_an_error() {
+        let (client_fd, mut sway_fd) = UnixStream::pair().unwrap();
+        let sway = thread::spawn(move || {
+            read_request(&mut sway_fd);
+            write_message(&mut sway_fd, 2, "{ \"success\": false }");
+        });
+        let result = Subscription::new()
+            .event(IPCEvents::Workspace)
+            .subscribe_on(client_fd);
+        sway.join().unwrap();
+        assert!(matches!(result, Err(IPCError::SubscribeError)));
+    }
 
-fn ws_focus_handler(fd_mutex: Arc<Mutex<UnixStream>>, tx: &mpsc::Sender<IPCMessages>, ws_rx: &mpsc::Receiver<String>) -> Result<(), IPCError> {
-    tx.send(IPCMessages::GetWorkspaces).unwrap();
-    let buf_string_json: String = ws_rx.recv().unwrap();
-    //println!("{}", buf_string_json);
-    // TODO buf_string_json will probably be the result to print.
-    println!("{}", buf_string_json);
-    Ok(())
-}
+    #[test]
+    fn finds_socket_of_live_sway_and_i3() {
+        let runtime_dir = env::temp_dir().join(format!("eww_sway_ipc_test.{}", std::process::id()));
+        fs::create_dir_all(&runtime_dir).unwrap();
+        // our own pid is certainly alive, pid_max is at most 2^22 so the other one is not
+        let alive = runtime_dir.join(format!("sway-ipc.1000.{}.sock", std::process::id()));
+        let dead = runtime_dir.join("sway-ipc.1000.99999999.sock");
+        let other_user = runtime_dir.join(format!("sway-ipc.1001.{}.sock", std::process::id()));
+        for path in [&dead, &alive, &other_user] {
+            fs::write(path, "").unwrap();
+        }
+        fs::write(runtime_dir.join("sway-ipc.1000.sock"), "").unwrap();
+
+        let i3_dir = runtime_dir.join("i3");
+        fs::create_dir_all(&i3_dir).unwrap();
+        let i3_alive = i3_dir.join(format!("ipc-socket.{}", std::process::id()));
+        fs::write(&i3_alive, "").unwrap();
+        fs::write(i3_dir.join("ipc-socket.99999999"), "").unwrap();
+
+        let found = find_sway_socket(&runtime_dir, 1000);
+        let found_i3 = find_i3_socket(&i3_dir);
+        fs::remove_dir_all(&runtime_dir).unwrap();
+        assert_eq!(found, Some(alive));
+        assert_eq!(found_i3, Some(i3_alive));
+        assert_eq!(
+            find_socket_path(Some(Path::new("/tmp/explicit.sock")), None).unwrap(),
+            PathBuf::from("/tmp/explicit.sock")
+        );
+    }
+
+    #[test]
+    fn stale_swaysock_falls_back_to_the_live_socket() {
+        let runtime_dir =
+            env::temp_dir().join(format!("eww_sway_ipc_stale.{}", std::process::id()));
+        fs::create_dir_all(&runtime_dir).unwrap();
+        // SWAYSOCK as sway left it before restarting with a new pid
+        let stale = runtime_dir.join("sway-ipc.1000.99999999.sock");
+        drop(UnixListener::bind(&stale).unwrap());
+        let from_env = vec![stale.clone()];
+        let found_before = find_socket(&from_env, &runtime_dir, 1000, None);
 
-fn client_state_mux(ipc_message: &str) -> Result<WorkspaceEventT, IPCError> {
-    return match &ipc_message[0..12] {
-        "{ \"change\": " => {
-            if &ipc_message[13..18] == "focus" {
-                return Ok(WorkspaceEventT::Focused);
-            } else if &ipc_message [13..17] == "init" {
-                return Ok(WorkspaceEventT::Initialized);
-            } else if &ipc_message [13..18] == "empty" {
-                return Ok(WorkspaceEventT::Empty);
-            } else {
-                return Err(IPCError::GeneralError);
+        let live = runtime_dir.join(format!("sway-ipc.1000.{}.sock", std::process::id()));
+        let _listener = UnixListener::bind(&live).unwrap();
+        let found = find_socket(&from_env, &runtime_dir, 1000, None);
+        // a live variable is still preferred
+        let found_env = find_socket(
+            std::slice::from_ref(&live),
+            &runtime_dir,
+            1000,
+            Some(WindowManager::I3),
+        );
+        // sockets named some other way only need to exist, as sockets
+        let custom = runtime_dir.join("sway.sock");
+        let _custom_listener = UnixListener::bind(&custom).unwrap();
+        let plain_file = runtime_dir.join("plain.sock");
+        fs::write(&plain_file, "").unwrap();
+        let custom_live = is_live_socket(&custom);
+        let plain_live = is_live_socket(&plain_file);
+        fs::remove_dir_all(&runtime_dir).unwrap();
+
+        assert!(custom_live);
+        assert!(!plain_live);
+        assert_eq!(found_before, Some(stale));
+        assert_eq!(found, Some(live.clone()));
+        assert_eq!(found_env, Some(live));
+    }
+
+    #[test]
+    fn closed_socket_is_unexpected_eof() {
+        let (client_fd, sway_fd) = UnixStream::pair().unwrap();
+        drop(sway_fd);
+        let client = IPCClient::from_stream(client_fd);
+        assert!(matches!(
+            client.get_version().unwrap_err(),
+            IPCError::WriteError(_) | IPCError::UnexpectedEof
+        ));
+
+        // a header announcing more payload than is sent before the socket closes
+        let (client_fd, mut sway_fd) = UnixStream::pair().unwrap();
+        let sway = thread::spawn(move || {
+            read_request(&mut sway_fd);
+            let mut message = MAGIC_STR.as_bytes().to_vec();
+            message.extend_from_slice(&100u32.to_ne_bytes());
+            message.extend_from_slice(&7u32.to_ne_bytes());
+            message.extend_from_slice(b"{ \"major\"");
+            sway_fd.write_all(&message).unwrap();
+        });
+        let client = IPCClient::from_stream(client_fd);
+        let error = client.get_version().unwrap_err();
+        sway.join().unwrap();
+        assert!(matches!(error, IPCError::UnexpectedEof));
+        assert_eq!(error.to_string(), "sway closed the connection");
+    }
+
+    // Sends a raw reply to whatever request arrives, for replies sway would never send
+    fn fake_sway_bytes(reply: Vec<u8>) -> (IPCClient, thread::JoinHandle<()>) {
+        let (client_fd, mut sway_fd) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || {
+            read_request(&mut sway_fd);
+            sway_fd.write_all(&reply).unwrap();
+        });
+        (IPCClient::from_stream(client_fd), handle)
+    }
+
+    #[test]
+    fn validates_reply_header() {
+        let mut reply = b"{ \"suc".to_vec();
+        reply.extend_from_slice(&[0u8; 8]);
+        let (client, sway) = fake_sway_bytes(reply);
+        let error = client.get_version().unwrap_err();
+        sway.join().unwrap();
+        assert!(matches!(error, IPCError::BadMagic(magic) if &magic == b"{ \"suc"));
+
+        let mut reply = MAGIC_STR.as_bytes().to_vec();
+        reply.extend_from_slice(&2u32.to_ne_bytes());
+        reply.extend_from_slice(&1u32.to_ne_bytes());
+        reply.extend_from_slice(b"[]");
+        let (client, sway) = fake_sway_bytes(reply);
+        let error = client.get_version().unwrap_err();
+        sway.join().unwrap();
+        assert!(matches!(
+            error,
+            IPCError::UnexpectedReplyType {
+                expected: 7,
+                got: 1
             }
+        ));
+    }
+
+    #[test]
+    fn refuses_replies_over_the_size_limit() {
+        let mut reply = MAGIC_STR.as_bytes().to_vec();
+        reply.extend_from_slice(&u32::MAX.to_ne_bytes());
+        reply.extend_from_slice(&4u32.to_ne_bytes());
+        let (client, sway) = fake_sway_bytes(reply);
+        let error = client.get_tree().unwrap_err();
+        sway.join().unwrap();
+        assert!(matches!(
+            error,
+            IPCError::PayloadTooLarge {
+                len: u32::MAX,
+                max: DEFAULT_MAX_PAYLOAD_LEN
+            }
+        ));
+
+        let (client, sway) = fake_sway("[ \"default\", \"resize\" ]");
+        let error = client.max_payload_len(8).get_binding_modes().unwrap_err();
+        sway.join().unwrap();
+        assert!(matches!(error, IPCError::PayloadTooLarge { max: 8, .. }));
+    }
+
+    #[test]
+    fn messages_round_trip_through_bytes() {
+        let messages = [
+            IPCFormat::new(IPCMessages::GetWorkspaces as u32),
+            IPCFormat::new(IPCMessages::Subscribe as u32).payload("[\"workspace\",\"window\"]"),
+            IPCFormat::new(IPCEvents::Window as u32)
+                .payload("{ \"change\": \"title\", \"name\": \"ünïcode\" }"),
+        ];
+        let mut bytes = vec![];
+        for message in &messages {
+            let encoded = message.encode();
+            assert_eq!(encoded.len(), HEADER_LEN + message.payload.len());
+            assert_eq!(
+                IPCFormat::decode(&encoded, DEFAULT_MAX_PAYLOAD_LEN).unwrap(),
+                (message.clone(), encoded.len())
+            );
+            bytes.extend(encoded);
+        }
+        // back to back messages decode one at a time
+        let mut rest = &bytes[..];
+        for message in &messages {
+            let (decoded, used) = IPCFormat::decode(rest, DEFAULT_MAX_PAYLOAD_LEN).unwrap();
+            assert_eq!(&decoded, message);
+            rest = &rest[used..];
         }
-        _ => Err(IPCError::GeneralError),
-   }
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn length_is_derived_from_the_payload() {
+        let message = IPCFormat::new(IPCMessages::Subscribe as u32).payload("[\"workspace\"]");
+        assert_eq!(message.payload_len(), 13);
+        let encoded = message.encode();
+        assert_eq!(&encoded[..6], b"i3-ipc");
+        assert_eq!(&encoded[6..10], &13u32.to_ne_bytes());
+        assert_eq!(&encoded[10..14], &2u32.to_ne_bytes());
+
+        // a truncated buffer is reported rather than decoded short
+        assert!(matches!(
+            IPCFormat::decode(&encoded[..encoded.len() - 1], DEFAULT_MAX_PAYLOAD_LEN),
+            Err(IPCError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            IPCFormat::decode(&encoded[..10], DEFAULT_MAX_PAYLOAD_LEN),
+            Err(IPCError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn i3_is_detected_and_sway_only_requests_are_refused() {
+        let (client, sway) = fake_sway("{\"major\":4,\"minor\":23,\"patch\":0,\"human_readable\":\"4.23\",\"loaded_config_file_name\":\"/home/user/.config/i3/config\"}");
+        assert_eq!(client.detect_wm().unwrap(), WindowManager::I3);
+        sway.join().unwrap();
+
+        // nothing is sent, the other end of the pair never answers
+        let (client_fd, _i3_fd) = UnixStream::pair().unwrap();
+        let client = IPCClient::from_stream(client_fd).wm(WindowManager::I3);
+        assert!(matches!(
+            client.get_inputs(),
+            Err(IPCError::UnsupportedError("GET_INPUTS"))
+        ));
+        assert!(matches!(
+            client.subscribe(&[IPCEvents::Workspace, IPCEvents::Input]),
+            Err(IPCError::UnsupportedError("input"))
+        ));
+    }
 }
@@ -0,0 +1,924 @@
+use crate::ipc_client::IPCEvents;
+use crate::json_parser::{self, JsonError, JsonObj, JsonValue};
+
+// Implemented by every typed reply that sway sends back as a json object
+pub trait FromJsonObj: Sized {
+    fn from_json_obj(obj: &JsonObj) -> Result<Self, JsonError>;
+}
+
+// Parses a reply that is a single json object
+pub fn from_json<T: FromJsonObj>(json: &str) -> Result<T, JsonError> {
+    match json_parser::parse(json)? {
+        JsonValue::Object(obj) => T::from_json_obj(&obj),
+        _ => Err(JsonError::StringToJsonError),
+    }
+}
+
+// Parses a reply that is a json list of objects
+pub fn list_from_json<T: FromJsonObj>(json: &str) -> Result<Vec<T>, JsonError> {
+    match json_parser::parse(json)? {
+        JsonValue::List(values) => values.iter().map(obj_from_value).collect(),
+        _ => Err(JsonError::StringToJsonListError),
+    }
+}
+
+// Parses a reply that is a json list of strings, eg. GET_MARKS
+pub fn strings_from_json(json: &str) -> Result<Vec<String>, JsonError> {
+    match json_parser::parse(json)? {
+        JsonValue::List(values) => values
+            .iter()
+            .map(|value| match value {
+                JsonValue::String(s) => Ok(s.clone()),
+                _ => Err(JsonError::InvalidTypeError),
+            })
+            .collect(),
+        _ => Err(JsonError::StringToJsonListError),
+    }
+}
+
+fn obj_from_value<T: FromJsonObj>(value: &JsonValue) -> Result<T, JsonError> {
+    match value {
+        JsonValue::Object(obj) => T::from_json_obj(obj),
+        _ => Err(JsonError::InvalidTypeError),
+    }
+}
+
+fn get_obj_list<T: FromJsonObj>(obj: &JsonObj, key: &str) -> Result<Vec<T>, JsonError> {
+    json_parser::get_list(obj, key)?
+        .iter()
+        .map(obj_from_value)
+        .collect()
+}
+
+// i3 leaves out some of the lists sway always sends, treat those as empty
+fn get_opt_obj_list<T: FromJsonObj>(obj: &JsonObj, key: &str) -> Result<Vec<T>, JsonError> {
+    match json_parser::get_opt(obj, key) {
+        Some(_) => get_obj_list(obj, key),
+        None => Ok(vec![]),
+    }
+}
+
+fn get_id_list(obj: &JsonObj, key: &str) -> Result<Vec<i64>, JsonError> {
+    json_parser::get_list(obj, key)?
+        .iter()
+        .map(|id| match id {
+            JsonValue::Number(n) => Ok(*n as i64),
+            _ => Err(JsonError::KeyTypeError(key.to_string())),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rect {
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+}
+
+impl FromJsonObj for Rect {
+    fn from_json_obj(obj: &JsonObj) -> Result<Rect, JsonError> {
+        Ok(Rect {
+            x: json_parser::get_num(obj, "x")? as i64,
+            y: json_parser::get_num(obj, "y")? as i64,
+            width: json_parser::get_num(obj, "width")? as i64,
+            height: json_parser::get_num(obj, "height")? as i64,
+        })
+    }
+}
+
+// A single entry of the GET_WORKSPACES reply, i3 does not send representation, focus or
+// floating_nodes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workspace {
+    pub id: i64,
+    // -1 for named workspaces that do not start with a number
+    pub num: i64,
+    pub name: String,
+    // the name as printed, without the "3:" of "3:web" with --strip-number
+    pub label: String,
+    pub output: String,
+    pub focused: bool,
+    pub visible: bool,
+    pub urgent: bool,
+    pub rect: Rect,
+    // null when the workspace has no windows
+    pub representation: Option<String>,
+    pub focus: Vec<i64>,
+    pub floating_nodes: usize,
+    // false for the placeholders of --persistent, which sway knows nothing about
+    pub exists: bool,
+    // app ids (or X11 classes) of its windows, without duplicates. Only filled in from GET_TREE
+    // with --icons, as are the icons they map to
+    pub apps: Vec<String>,
+    pub icons: Vec<String>,
+}
+
+impl FromJsonObj for Workspace {
+    fn from_json_obj(obj: &JsonObj) -> Result<Workspace, JsonError> {
+        let name = json_parser::get_string(obj, "name")?;
+        Ok(Workspace {
+            id: json_parser::get_num(obj, "id")? as i64,
+            num: json_parser::get_num(obj, "num")? as i64,
+            label: name.clone(),
+            name,
+            output: json_parser::get_string(obj, "output")?,
+            focused: json_parser::get_bool(obj, "focused")?,
+            visible: json_parser::get_bool(obj, "visible")?,
+            urgent: json_parser::get_bool(obj, "urgent")?,
+            rect: Rect::from_json_obj(json_parser::get_obj(obj, "rect")?)?,
+            representation: json_parser::get_opt_string(obj, "representation")?,
+            focus: match json_parser::get_opt(obj, "focus") {
+                Some(_) => get_id_list(obj, "focus")?,
+                None => vec![],
+            },
+            floating_nodes: match json_parser::get_opt(obj, "floating_nodes") {
+                Some(_) => json_parser::get_list(obj, "floating_nodes")?.len(),
+                None => 0,
+            },
+            exists: true,
+            apps: vec![],
+            icons: vec![],
+        })
+    }
+}
+
+// A single entry of the RUN_COMMAND reply, one per command in the payload
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandResult {
+    pub success: bool,
+    pub parse_error: bool,
+    pub error: Option<String>,
+}
+
+impl FromJsonObj for CommandResult {
+    fn from_json_obj(obj: &JsonObj) -> Result<CommandResult, JsonError> {
+        Ok(CommandResult {
+            success: json_parser::get_bool(obj, "success")?,
+            parse_error: json_parser::get_opt_bool(obj, "parse_error")?.unwrap_or(false),
+            error: json_parser::get_opt_string(obj, "error")?,
+        })
+    }
+}
+
+// The { "success": bool } reply shared by SUBSCRIBE, SEND_TICK and SYNC
+#[derive(Debug, Clone, PartialEq)]
+pub struct Success {
+    pub success: bool,
+}
+
+impl FromJsonObj for Success {
+    fn from_json_obj(obj: &JsonObj) -> Result<Success, JsonError> {
+        Ok(Success {
+            success: json_parser::get_bool(obj, "success")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputMode {
+    pub width: i64,
+    pub height: i64,
+    // in mHz
+    pub refresh: i64,
+}
+
+impl FromJsonObj for OutputMode {
+    fn from_json_obj(obj: &JsonObj) -> Result<OutputMode, JsonError> {
+        Ok(OutputMode {
+            width: json_parser::get_num(obj, "width")? as i64,
+            height: json_parser::get_num(obj, "height")? as i64,
+            refresh: json_parser::get_num(obj, "refresh")? as i64,
+        })
+    }
+}
+
+// A single entry of the GET_OUTPUTS reply. Disabled outputs omit most of the optional fields
+// and i3 only sends name, active, primary, current_workspace and rect
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    pub name: String,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub active: bool,
+    pub primary: bool,
+    pub scale: Option<f64>,
+    pub transform: Option<String>,
+    pub current_workspace: Option<String>,
+    pub modes: Vec<OutputMode>,
+    pub current_mode: Option<OutputMode>,
+    pub rect: Rect,
+}
+
+impl FromJsonObj for Output {
+    fn from_json_obj(obj: &JsonObj) -> Result<Output, JsonError> {
+        let current_mode = match json_parser::get_opt_obj(obj, "current_mode")? {
+            Some(mode) => Some(OutputMode::from_json_obj(mode)?),
+            None => None,
+        };
+        Ok(Output {
+            name: json_parser::get_string(obj, "name")?,
+            make: json_parser::get_opt_string(obj, "make")?,
+            model: json_parser::get_opt_string(obj, "model")?,
+            serial: json_parser::get_opt_string(obj, "serial")?,
+            active: json_parser::get_bool(obj, "active")?,
+            primary: json_parser::get_bool(obj, "primary")?,
+            scale: json_parser::get_opt_num(obj, "scale")?,
+            transform: json_parser::get_opt_string(obj, "transform")?,
+            current_workspace: json_parser::get_opt_string(obj, "current_workspace")?,
+            modes: get_opt_obj_list(obj, "modes")?,
+            current_mode,
+            rect: Rect::from_json_obj(json_parser::get_obj(obj, "rect")?)?,
+        })
+    }
+}
+
+// X11 properties, only present for xwayland windows
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowProperties {
+    pub title: Option<String>,
+    pub class: Option<String>,
+    pub instance: Option<String>,
+    pub window_role: Option<String>,
+}
+
+impl FromJsonObj for WindowProperties {
+    fn from_json_obj(obj: &JsonObj) -> Result<WindowProperties, JsonError> {
+        Ok(WindowProperties {
+            title: json_parser::get_opt_string(obj, "title")?,
+            class: json_parser::get_opt_string(obj, "class")?,
+            instance: json_parser::get_opt_string(obj, "instance")?,
+            window_role: json_parser::get_opt_string(obj, "window_role")?,
+        })
+    }
+}
+
+// A node of the GET_TREE reply: root, output, workspace, con or floating_con
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub id: i64,
+    pub name: Option<String>,
+    pub node_type: String,
+    pub layout: String,
+    pub rect: Rect,
+    pub urgent: bool,
+    pub focused: bool,
+    pub marks: Vec<String>,
+    pub fullscreen_mode: i64,
+    pub focus: Vec<i64>,
+    pub nodes: Vec<Node>,
+    pub floating_nodes: Vec<Node>,
+    // workspace only
+    pub num: Option<i64>,
+    pub output: Option<String>,
+    // window only, app_id is set for wayland windows and window_properties for xwayland ones
+    pub app_id: Option<String>,
+    pub pid: Option<i64>,
+    pub window: Option<i64>,
+    pub window_properties: Option<WindowProperties>,
+}
+
+impl FromJsonObj for Node {
+    fn from_json_obj(obj: &JsonObj) -> Result<Node, JsonError> {
+        let window_properties = match json_parser::get_opt_obj(obj, "window_properties")? {
+            Some(properties) => Some(WindowProperties::from_json_obj(properties)?),
+            None => None,
+        };
+        Ok(Node {
+            id: json_parser::get_num(obj, "id")? as i64,
+            name: json_parser::get_opt_string(obj, "name")?,
+            node_type: json_parser::get_string(obj, "type")?,
+            layout: json_parser::get_string(obj, "layout")?,
+            rect: Rect::from_json_obj(json_parser::get_obj(obj, "rect")?)?,
+            urgent: json_parser::get_bool(obj, "urgent")?,
+            focused: json_parser::get_bool(obj, "focused")?,
+            marks: json_parser::get_string_list(obj, "marks")?,
+            fullscreen_mode: json_parser::get_opt_num(obj, "fullscreen_mode")?.unwrap_or(0f64)
+                as i64,
+            focus: get_id_list(obj, "focus")?,
+            nodes: get_obj_list(obj, "nodes")?,
+            floating_nodes: get_obj_list(obj, "floating_nodes")?,
+            num: json_parser::get_opt_num(obj, "num")?.map(|n| n as i64),
+            output: json_parser::get_opt_string(obj, "output")?,
+            app_id: json_parser::get_opt_string(obj, "app_id")?,
+            pid: json_parser::get_opt_num(obj, "pid")?.map(|n| n as i64),
+            window: json_parser::get_opt_num(obj, "window")?.map(|n| n as i64),
+            window_properties,
+        })
+    }
+}
+
+impl Node {
+    // Views are the only nodes with a pid or an X11 window id, splits and workspaces have
+    // neither. i3 does not send pids at all
+    pub fn is_window(&self) -> bool {
+        self.pid.is_some() || self.window.is_some()
+    }
+
+    pub fn is_floating(&self) -> bool {
+        self.node_type == "floating_con"
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen_mode != 0
+    }
+
+    // The app_id of wayland windows or the class of X11 ones
+    pub fn app(&self) -> Option<&str> {
+        self.app_id.as_deref().or_else(|| {
+            self.window_properties
+                .as_ref()
+                .and_then(|properties| properties.class.as_deref())
+        })
+    }
+
+    // Every window below this node, tiling ones before floating ones
+    pub fn windows(&self) -> Vec<&Node> {
+        let mut windows = vec![];
+        for node in self.nodes.iter().chain(self.floating_nodes.iter()) {
+            if node.is_window() {
+                windows.push(node);
+            }
+            windows.extend(node.windows());
+        }
+        windows
+    }
+
+    // Every workspace below this node, eg. the root of GET_TREE
+    pub fn workspaces(&self) -> Vec<&Node> {
+        if self.node_type == "workspace" {
+            return vec![self];
+        }
+        self.nodes.iter().flat_map(Node::workspaces).collect()
+    }
+
+    // Depth first search for the node that currently has focus
+    pub fn find_focused(&self) -> Option<&Node> {
+        if self.focused {
+            return Some(self);
+        }
+        self.nodes
+            .iter()
+            .chain(self.floating_nodes.iter())
+            .find_map(|node| node.find_focused())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Version {
+    pub major: i64,
+    pub minor: i64,
+    pub patch: i64,
+    pub human_readable: String,
+    pub loaded_config_file_name: String,
+    // "sway" for sway, i3 does not send it
+    pub variant: Option<String>,
+}
+
+impl FromJsonObj for Version {
+    fn from_json_obj(obj: &JsonObj) -> Result<Version, JsonError> {
+        Ok(Version {
+            major: json_parser::get_num(obj, "major")? as i64,
+            minor: json_parser::get_num(obj, "minor")? as i64,
+            patch: json_parser::get_num(obj, "patch")? as i64,
+            human_readable: json_parser::get_string(obj, "human_readable")?,
+            loaded_config_file_name: json_parser::get_string(obj, "loaded_config_file_name")?,
+            variant: json_parser::get_opt_string(obj, "variant")?,
+        })
+    }
+}
+
+// The GET_BAR_CONFIG reply for a single bar id
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarConfig {
+    pub id: String,
+    pub mode: String,
+    pub position: String,
+    pub status_command: Option<String>,
+    pub font: String,
+    pub workspace_buttons: bool,
+    pub binding_mode_indicator: bool,
+    pub verbose: bool,
+}
+
+impl FromJsonObj for BarConfig {
+    fn from_json_obj(obj: &JsonObj) -> Result<BarConfig, JsonError> {
+        Ok(BarConfig {
+            id: json_parser::get_string(obj, "id")?,
+            mode: json_parser::get_string(obj, "mode")?,
+            position: json_parser::get_string(obj, "position")?,
+            status_command: json_parser::get_opt_string(obj, "status_command")?,
+            font: json_parser::get_string(obj, "font")?,
+            workspace_buttons: json_parser::get_bool(obj, "workspace_buttons")?,
+            binding_mode_indicator: json_parser::get_bool(obj, "binding_mode_indicator")?,
+            verbose: json_parser::get_bool(obj, "verbose")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub config: String,
+}
+
+impl FromJsonObj for Config {
+    fn from_json_obj(obj: &JsonObj) -> Result<Config, JsonError> {
+        Ok(Config {
+            config: json_parser::get_string(obj, "config")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindingState {
+    pub name: String,
+}
+
+impl FromJsonObj for BindingState {
+    fn from_json_obj(obj: &JsonObj) -> Result<BindingState, JsonError> {
+        Ok(BindingState {
+            name: json_parser::get_string(obj, "name")?,
+        })
+    }
+}
+
+// A single entry of the GET_INPUTS reply, the xkb fields are only set for keyboards
+#[derive(Debug, Clone, PartialEq)]
+pub struct Input {
+    pub identifier: String,
+    pub name: String,
+    pub input_type: String,
+    pub vendor: i64,
+    pub product: i64,
+    pub xkb_active_layout_name: Option<String>,
+    pub xkb_active_layout_index: Option<i64>,
+    pub xkb_layout_names: Vec<String>,
+}
+
+impl FromJsonObj for Input {
+    fn from_json_obj(obj: &JsonObj) -> Result<Input, JsonError> {
+        let xkb_layout_names = match json_parser::get_opt(obj, "xkb_layout_names") {
+            Some(_) => json_parser::get_string_list(obj, "xkb_layout_names")?,
+            None => vec![],
+        };
+        Ok(Input {
+            identifier: json_parser::get_string(obj, "identifier")?,
+            name: json_parser::get_string(obj, "name")?,
+            input_type: json_parser::get_string(obj, "type")?,
+            vendor: json_parser::get_num(obj, "vendor")? as i64,
+            product: json_parser::get_num(obj, "product")? as i64,
+            xkb_active_layout_name: json_parser::get_opt_string(obj, "xkb_active_layout_name")?,
+            xkb_active_layout_index: json_parser::get_opt_num(obj, "xkb_active_layout_index")?
+                .map(|n| n as i64),
+            xkb_layout_names,
+        })
+    }
+}
+
+// A single entry of the GET_SEATS reply
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seat {
+    pub name: String,
+    pub capabilities: i64,
+    // id of the focused node, 0 when nothing is focused
+    pub focus: i64,
+    pub devices: Vec<Input>,
+}
+
+impl FromJsonObj for Seat {
+    fn from_json_obj(obj: &JsonObj) -> Result<Seat, JsonError> {
+        Ok(Seat {
+            name: json_parser::get_string(obj, "name")?,
+            capabilities: json_parser::get_num(obj, "capabilities")? as i64,
+            focus: json_parser::get_num(obj, "focus")? as i64,
+            devices: get_obj_list(obj, "devices")?,
+        })
+    }
+}
+
+// The binding that triggered a binding event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub command: String,
+    pub event_state_mask: Vec<String>,
+    pub input_code: i64,
+    // null for mouse bindings and bindcode bindings
+    pub symbol: Option<String>,
+    pub input_type: String,
+}
+
+impl FromJsonObj for Binding {
+    fn from_json_obj(obj: &JsonObj) -> Result<Binding, JsonError> {
+        Ok(Binding {
+            command: json_parser::get_string(obj, "command")?,
+            event_state_mask: json_parser::get_string_list(obj, "event_state_mask")?,
+            input_code: json_parser::get_num(obj, "input_code")? as i64,
+            symbol: json_parser::get_opt_string(obj, "symbol")?,
+            input_type: json_parser::get_string(obj, "input_type")?,
+        })
+    }
+}
+
+fn get_opt_node(obj: &JsonObj, key: &str) -> Result<Option<Node>, JsonError> {
+    match json_parser::get_opt_obj(obj, key)? {
+        Some(node) => Ok(Some(Node::from_json_obj(node)?)),
+        None => Ok(None),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkspaceChange {
+    Init,
+    Empty,
+    Focus,
+    Move,
+    Rename,
+    Urgent,
+    Reload,
+    // Anything newer than this list, kept so callers can log or ignore it
+    Unknown(String),
+}
+
+impl WorkspaceChange {
+    pub fn from_name(change: &str) -> WorkspaceChange {
+        match change {
+            "init" => WorkspaceChange::Init,
+            "empty" => WorkspaceChange::Empty,
+            "focus" => WorkspaceChange::Focus,
+            "move" => WorkspaceChange::Move,
+            "rename" => WorkspaceChange::Rename,
+            "urgent" => WorkspaceChange::Urgent,
+            "reload" => WorkspaceChange::Reload,
+            _ => WorkspaceChange::Unknown(change.to_string()),
+        }
+    }
+}
+
+// current and old are the full workspace nodes, either can be null depending on the change
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceEvent {
+    pub change: WorkspaceChange,
+    pub current: Option<Node>,
+    pub old: Option<Node>,
+}
+
+impl FromJsonObj for WorkspaceEvent {
+    fn from_json_obj(obj: &JsonObj) -> Result<WorkspaceEvent, JsonError> {
+        Ok(WorkspaceEvent {
+            change: WorkspaceChange::from_name(&json_parser::get_string(obj, "change")?),
+            current: get_opt_node(obj, "current")?,
+            old: get_opt_node(obj, "old")?,
+        })
+    }
+}
+
+// Sway only ever sends "unspecified" as the change, the outputs have to be queried again
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputEvent {
+    pub change: String,
+}
+
+impl FromJsonObj for OutputEvent {
+    fn from_json_obj(obj: &JsonObj) -> Result<OutputEvent, JsonError> {
+        Ok(OutputEvent {
+            change: json_parser::get_string(obj, "change")?,
+        })
+    }
+}
+
+// change is the name of the binding mode that was switched to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeEvent {
+    pub change: String,
+    pub pango_markup: bool,
+}
+
+impl FromJsonObj for ModeEvent {
+    fn from_json_obj(obj: &JsonObj) -> Result<ModeEvent, JsonError> {
+        Ok(ModeEvent {
+            change: json_parser::get_string(obj, "change")?,
+            pango_markup: json_parser::get_bool(obj, "pango_markup")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowEvent {
+    pub change: String,
+    pub container: Node,
+}
+
+impl FromJsonObj for WindowEvent {
+    fn from_json_obj(obj: &JsonObj) -> Result<WindowEvent, JsonError> {
+        Ok(WindowEvent {
+            change: json_parser::get_string(obj, "change")?,
+            container: Node::from_json_obj(json_parser::get_obj(obj, "container")?)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindingEvent {
+    pub change: String,
+    pub binding: Binding,
+}
+
+impl FromJsonObj for BindingEvent {
+    fn from_json_obj(obj: &JsonObj) -> Result<BindingEvent, JsonError> {
+        Ok(BindingEvent {
+            change: json_parser::get_string(obj, "change")?,
+            binding: Binding::from_json_obj(json_parser::get_obj(obj, "binding")?)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShutdownEvent {
+    pub change: String,
+}
+
+impl FromJsonObj for ShutdownEvent {
+    fn from_json_obj(obj: &JsonObj) -> Result<ShutdownEvent, JsonError> {
+        Ok(ShutdownEvent {
+            change: json_parser::get_string(obj, "change")?,
+        })
+    }
+}
+
+// first is only set on the tick sent right after subscribing
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickEvent {
+    pub first: bool,
+    pub payload: String,
+}
+
+impl FromJsonObj for TickEvent {
+    fn from_json_obj(obj: &JsonObj) -> Result<TickEvent, JsonError> {
+        Ok(TickEvent {
+            first: json_parser::get_bool(obj, "first")?,
+            payload: json_parser::get_string(obj, "payload")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarStateUpdateEvent {
+    pub id: String,
+    pub visible_by_modifier: bool,
+}
+
+impl FromJsonObj for BarStateUpdateEvent {
+    fn from_json_obj(obj: &JsonObj) -> Result<BarStateUpdateEvent, JsonError> {
+        Ok(BarStateUpdateEvent {
+            id: json_parser::get_string(obj, "id")?,
+            visible_by_modifier: json_parser::get_bool(obj, "visible_by_modifier")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputEvent {
+    pub change: String,
+    pub input: Input,
+}
+
+impl FromJsonObj for InputEvent {
+    fn from_json_obj(obj: &JsonObj) -> Result<InputEvent, JsonError> {
+        Ok(InputEvent {
+            change: json_parser::get_string(obj, "change")?,
+            input: Input::from_json_obj(json_parser::get_obj(obj, "input")?)?,
+        })
+    }
+}
+
+// An event decoded according to the type field of its message header
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Workspace(Box<WorkspaceEvent>),
+    Output(OutputEvent),
+    Mode(ModeEvent),
+    Window(Box<WindowEvent>),
+    BarConfigUpdate(Box<BarConfig>),
+    Binding(BindingEvent),
+    Shutdown(ShutdownEvent),
+    Tick(TickEvent),
+    BarStateUpdate(BarStateUpdateEvent),
+    Input(Box<InputEvent>),
+}
+
+impl Event {
+    pub fn from_json(event_type: IPCEvents, json: &str) -> Result<Event, JsonError> {
+        Ok(match event_type {
+            IPCEvents::Workspace => Event::Workspace(Box::new(from_json(json)?)),
+            IPCEvents::Output => Event::Output(from_json(json)?),
+            IPCEvents::Mode => Event::Mode(from_json(json)?),
+            IPCEvents::Window => Event::Window(Box::new(from_json(json)?)),
+            IPCEvents::BarConfigUpdate => Event::BarConfigUpdate(Box::new(from_json(json)?)),
+            IPCEvents::Binding => Event::Binding(from_json(json)?),
+            IPCEvents::Shutdown => Event::Shutdown(from_json(json)?),
+            IPCEvents::Tick => Event::Tick(from_json(json)?),
+            IPCEvents::BarStateUpdate => Event::BarStateUpdate(from_json(json)?),
+            IPCEvents::Input => Event::Input(Box::new(from_json(json)?)),
+        })
+    }
+}
+
+// Workspaces and the json sway sends about them, for the tests of every module working with them
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    // A workspace on eDP-1, numbered as sway numbers it and visible when it is focused
+    pub fn workspace(name: &str, focused: bool, urgent: bool) -> Workspace {
+        let digits: String = name.chars().take_while(char::is_ascii_digit).collect();
+        Workspace {
+            id: 1,
+            num: digits.parse().unwrap_or(-1),
+            name: name.to_string(),
+            label: name.to_string(),
+            output: String::from("eDP-1"),
+            focused,
+            visible: focused,
+            urgent,
+            rect: Rect {
+                x: 0,
+                y: 0,
+                width: 1920,
+                height: 1080,
+            },
+            representation: None,
+            focus: vec![],
+            floating_nodes: 0,
+            exists: true,
+            apps: vec![],
+            icons: vec![],
+        }
+    }
+
+    fn rect_json(rect: &Rect) -> String {
+        format!(
+            "{{ \"x\": {}, \"y\": {}, \"width\": {}, \"height\": {} }}",
+            rect.x, rect.y, rect.width, rect.height
+        )
+    }
+
+    // The GET_WORKSPACES reply listing the workspaces
+    pub fn workspaces_json(workspaces: &[Workspace]) -> String {
+        let entries: Vec<String> = workspaces
+            .iter()
+            .map(|workspace| {
+                format!(
+                    "{{ \"id\": {}, \"num\": {}, \"name\": \"{}\", \"output\": \"{}\", \"focused\": {}, \"visible\": {}, \"urgent\": {}, \"rect\": {} }}",
+                    workspace.id,
+                    workspace.num,
+                    workspace.name,
+                    workspace.output,
+                    workspace.focused,
+                    workspace.visible,
+                    workspace.urgent,
+                    rect_json(&workspace.rect)
+                )
+            })
+            .collect();
+        format!("[ {} ]", entries.join(", "))
+    }
+
+    // A workspace event with the workspace as current, holding no windows
+    pub fn workspace_event(change: &str, workspace: &Workspace) -> Event {
+        let json = format!(
+            "{{ \"change\": \"{change}\", \"old\": null, \"current\": {{ \"id\": {}, \"type\": \"workspace\", \"name\": \"{}\", \"layout\": \"splith\", \"rect\": {}, \"urgent\": {}, \"focused\": {}, \"marks\": [ ], \"focus\": [ ], \"nodes\": [ ], \"floating_nodes\": [ ], \"num\": {}, \"output\": \"{}\" }} }}",
+            workspace.id,
+            workspace.name,
+            rect_json(&workspace.rect),
+            workspace.urgent,
+            workspace.focused,
+            workspace.num,
+            workspace.output
+        );
+        Event::from_json(IPCEvents::Workspace, &json).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WORKSPACE_JSON: &str = "{ \"id\": 36, \"type\": \"workspace\", \"orientation\": \"horizontal\", \"percent\": null, \"urgent\": false, \"marks\": [ ], \"layout\": \"splith\", \"border\": \"none\", \"current_border_width\": 0, \"rect\": { \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }, \"deco_rect\": { \"x\": 0, \"y\": 0, \"width\": 0, \"height\": 0 }, \"window_rect\": { \"x\": 0, \"y\": 0, \"width\": 0, \"height\": 0 }, \"geometry\": { \"x\": 0, \"y\": 0, \"width\": 0, \"height\": 0 }, \"name\": \"2\", \"window\": null, \"nodes\": [ ], \"floating_nodes\": [ ], \"focus\": [ 39, 41 ], \"fullscreen_mode\": 1, \"sticky\": false, \"floating\": null, \"scratchpad_state\": null, \"num\": 2, \"output\": \"eDP-1\", \"representation\": \"H[T[foot obsidian]]\", \"focused\": true, \"visible\": true }";
+
+    #[test]
+    fn workspace_from_json_obj_by_key() {
+        let input = format!("[{WORKSPACE_JSON}]");
+        let workspaces = list_from_json::<Workspace>(&input).unwrap();
+        assert_eq!(workspaces.len(), 1);
+        let ws = &workspaces[0];
+        assert_eq!(ws.id, 36);
+        assert_eq!(ws.num, 2);
+        assert_eq!(ws.name, "2");
+        assert_eq!(ws.output, "eDP-1");
+        assert!(ws.focused);
+        assert!(ws.visible);
+        assert!(!ws.urgent);
+        assert_eq!(ws.rect.width, 1920);
+        assert_eq!(ws.representation.as_deref(), Some("H[T[foot obsidian]]"));
+        assert_eq!(ws.focus, vec![39, 41]);
+        assert_eq!(ws.floating_nodes, 0);
+    }
+
+    #[test]
+    fn workspace_field_order_does_not_matter() {
+        let input = String::from("[{\"focused\":false,\"visible\":true,\"urgent\":true,\"output\":\"HDMI-A-1\",\"name\":\"3:web\",\"num\":3,\"id\":7,\"rect\":{\"x\":1920,\"y\":0,\"width\":1920,\"height\":1080},\"representation\":null,\"focus\":[],\"floating_nodes\":[]}]");
+        let ws = &list_from_json::<Workspace>(&input).unwrap()[0];
+        assert_eq!(ws.name, "3:web");
+        assert_eq!(ws.output, "HDMI-A-1");
+        assert!(ws.urgent);
+        assert_eq!(ws.rect.x, 1920);
+        assert_eq!(ws.representation, None);
+    }
+
+    #[test]
+    fn workspace_missing_key_is_named() {
+        let input = String::from("[{\"id\":7,\"num\":3,\"output\":\"eDP-1\"}]");
+        match list_from_json::<Workspace>(&input).unwrap_err() {
+            JsonError::MissingKeyError(key) => assert_eq!(key, "name"),
+            e => panic!("expected a missing key error, got {e:?}"),
+        }
+    }
+
+    #[test]
+    fn disabled_output_omits_optional_fields() {
+        let input = "[ { \"id\": 5, \"type\": \"output\", \"name\": \"HDMI-A-1\", \"make\": \"Dell Inc.\", \"model\": \"DELL U2419H\", \"serial\": \"ABC123\", \"active\": false, \"dpms\": false, \"power\": false, \"primary\": false, \"current_workspace\": null, \"modes\": [ { \"width\": 1920, \"height\": 1080, \"refresh\": 60000, \"picture_aspect_ratio\": \"none\" } ], \"rect\": { \"x\": 0, \"y\": 0, \"width\": 0, \"height\": 0 } } ]";
+        let output = &list_from_json::<Output>(input).unwrap()[0];
+        assert_eq!(output.name, "HDMI-A-1");
+        assert!(!output.active);
+        assert_eq!(output.scale, None);
+        assert_eq!(output.current_mode, None);
+        assert_eq!(output.current_workspace, None);
+        assert_eq!(output.modes[0].refresh, 60000);
+    }
+
+    #[test]
+    fn workspace_event_with_null_old() {
+        let input = "{ \"change\": \"init\", \"old\": null, \"current\": { \"id\": 12, \"type\": \"workspace\", \"name\": \"4\", \"layout\": \"splith\", \"rect\": { \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }, \"urgent\": false, \"focused\": false, \"marks\": [ ], \"focus\": [ ], \"nodes\": [ ], \"floating_nodes\": [ ], \"num\": 4, \"output\": \"eDP-1\" } }";
+        match Event::from_json(IPCEvents::Workspace, input).unwrap() {
+            Event::Workspace(event) => {
+                assert_eq!(event.change, WorkspaceChange::Init);
+                assert_eq!(event.old, None);
+                let current = event.current.unwrap();
+                assert_eq!(current.num, Some(4));
+                assert_eq!(current.output.as_deref(), Some("eDP-1"));
+            }
+            e => panic!("expected a workspace event, got {e:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_i3_replies() {
+        let input = "[{\"id\":94262803727104,\"num\":1,\"name\":\"1: web\",\"visible\":true,\"focused\":true,\"rect\":{\"x\":0,\"y\":20,\"width\":1920,\"height\":1060},\"output\":\"DP-1\",\"urgent\":false}]";
+        let workspace = &list_from_json::<Workspace>(input).unwrap()[0];
+        assert_eq!(workspace.name, "1: web");
+        assert_eq!(workspace.representation, None);
+        assert!(workspace.focus.is_empty());
+        assert_eq!(workspace.floating_nodes, 0);
+
+        let input = "[{\"name\":\"xroot-0\",\"active\":false,\"primary\":false,\"rect\":{\"x\":0,\"y\":0,\"width\":1920,\"height\":1080},\"current_workspace\":null},{\"name\":\"DP-1\",\"active\":true,\"primary\":true,\"rect\":{\"x\":0,\"y\":0,\"width\":1920,\"height\":1080},\"current_workspace\":\"1: web\"}]";
+        let outputs = list_from_json::<Output>(input).unwrap();
+        assert_eq!(outputs[1].current_workspace.as_deref(), Some("1: web"));
+        assert_eq!(outputs[1].make, None);
+        assert!(outputs[1].modes.is_empty());
+
+        let input = "{\"major\":4,\"minor\":23,\"patch\":0,\"human_readable\":\"4.23 (2023-10-29)\",\"loaded_config_file_name\":\"/home/user/.config/i3/config\"}";
+        assert_eq!(from_json::<Version>(input).unwrap().variant, None);
+    }
+
+    // A window as sway sends it in GET_TREE, with every key a real one has
+    fn window_json(id: i64, app_id: &str) -> String {
+        format!("{{ \"id\": {id}, \"type\": \"con\", \"orientation\": \"none\", \"percent\": 0.5, \"urgent\": false, \"marks\": [ ], \"focused\": false, \"layout\": \"none\", \"border\": \"pixel\", \"current_border_width\": 2, \"rect\": {{ \"x\": 0, \"y\": 0, \"width\": 960, \"height\": 1080 }}, \"deco_rect\": {{ \"x\": 0, \"y\": 0, \"width\": 0, \"height\": 0 }}, \"window_rect\": {{ \"x\": 2, \"y\": 2, \"width\": 956, \"height\": 1076 }}, \"geometry\": {{ \"x\": 0, \"y\": 0, \"width\": 1280, \"height\": 720 }}, \"name\": \"~\\/src \\u2014 {app_id} \\\"{id}\\\"\", \"window\": null, \"nodes\": [ ], \"floating_nodes\": [ ], \"focus\": [ ], \"fullscreen_mode\": 0, \"sticky\": false, \"floating\": \"auto_off\", \"scratchpad_state\": \"none\", \"pid\": {id}, \"app_id\": \"{app_id}\", \"foreign_toplevel_identifier\": \"2f6c5e0c8a3b4d1e9f7a6b5c4d3e2f1{id}\", \"visible\": true, \"max_render_time\": 0, \"allow_tearing\": false, \"shell\": \"xdg_shell\", \"inhibit_idle\": false, \"idle_inhibitors\": {{ \"user\": \"none\", \"application\": \"none\" }} }}")
+    }
+
+    #[test]
+    fn parses_a_tree_with_hundreds_of_windows() {
+        let workspaces: Vec<String> = (0..20)
+            .map(|ws| {
+                let windows: Vec<String> = (0..25)
+                    .map(|window| window_json(1000 + ws * 25 + window, "foot"))
+                    .collect();
+                format!("{{ \"id\": {ws}, \"type\": \"workspace\", \"name\": \"{ws}\", \"layout\": \"splith\", \"rect\": {{ \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }}, \"urgent\": false, \"focused\": false, \"marks\": [ ], \"focus\": [ ], \"num\": {ws}, \"output\": \"eDP-1\", \"nodes\": [ {{ \"id\": {}, \"type\": \"con\", \"name\": null, \"layout\": \"tabbed\", \"rect\": {{ \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }}, \"urgent\": false, \"focused\": false, \"marks\": [ ], \"focus\": [ ], \"nodes\": [ {} ], \"floating_nodes\": [ ] }} ], \"floating_nodes\": [ {} ] }}", 100 + ws, windows[1..].join(", "), windows[0])
+            })
+            .collect();
+        let input = format!("{{ \"id\": 1, \"type\": \"root\", \"name\": \"root\", \"layout\": \"splith\", \"rect\": {{ \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }}, \"urgent\": false, \"focused\": false, \"marks\": [ ], \"focus\": [ 3 ], \"floating_nodes\": [ ], \"nodes\": [ {{ \"id\": 3, \"type\": \"output\", \"name\": \"eDP-1\", \"layout\": \"output\", \"rect\": {{ \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }}, \"urgent\": false, \"focused\": false, \"marks\": [ ], \"focus\": [ ], \"floating_nodes\": [ ], \"nodes\": [ {} ] }} ] }}", workspaces.join(", "));
+        assert!(input.len() > 400_000);
+
+        let tree = from_json::<Node>(&input).unwrap();
+        assert_eq!(tree.workspaces().len(), 20);
+        let windows = tree.windows();
+        assert_eq!(windows.len(), 500);
+        assert!(windows.iter().all(|window| window.app() == Some("foot")));
+        assert_eq!(
+            windows[0].name.as_deref(),
+            Some("~\\/src \\u2014 foot \\\"1001\\\"")
+        );
+        // floating windows come after the tiling ones of their workspace
+        assert_eq!(windows[24].id, 1000);
+    }
+}
@@ -3,7 +3,6 @@ use std::error::Error;
 use std::fmt;
 use std::rc::Rc;
 
-// TODO investigate error types later
 #[derive(Debug)]
 pub enum JsonError {
     StringToJsonError,
@@ -12,10 +11,21 @@ pub enum JsonError {
     RanOutOfCharsError,
     InvalidTypeError,
     InvalidNumberError,
+    MissingKeyError(String),
+    KeyTypeError(String),
 }
 impl fmt::Display for JsonError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "something went wrong")
+        match self {
+            JsonError::StringToJsonError => write!(f, "expected a json object"),
+            JsonError::StringToJsonListError => write!(f, "expected a json list"),
+            JsonError::InvalidSyntaxError => write!(f, "invalid json syntax"),
+            JsonError::RanOutOfCharsError => write!(f, "json ended unexpectedly"),
+            JsonError::InvalidTypeError => write!(f, "invalid json value"),
+            JsonError::InvalidNumberError => write!(f, "invalid json number"),
+            JsonError::MissingKeyError(key) => write!(f, "missing key \"{key}\""),
+            JsonError::KeyTypeError(key) => write!(f, "unexpected type for key \"{key}\""),
+        }
     }
 }
 impl Error for JsonError {}
@@ -46,6 +56,170 @@ pub struct JsonKVPair {
 
 pub type JsonObj = Vec<JsonKVPair>;
 
+// Looks up the value stored under key, erroring with the key name if it is absent
+pub fn get<'a>(obj: &'a JsonObj, key: &str) -> Result<&'a JsonValue, JsonError> {
+    obj.iter()
+        .find(|pair| pair.key == key)
+        .map(|pair| &pair.value)
+        .ok_or_else(|| JsonError::MissingKeyError(key.to_string()))
+}
+
+pub fn get_string(obj: &JsonObj, key: &str) -> Result<String, JsonError> {
+    match get(obj, key)? {
+        JsonValue::String(s) => Ok(s.clone()),
+        _ => Err(JsonError::KeyTypeError(key.to_string())),
+    }
+}
+
+// null and absent keys are both treated as None
+pub fn get_opt<'a>(obj: &'a JsonObj, key: &str) -> Option<&'a JsonValue> {
+    match get(obj, key) {
+        Ok(JsonValue::Null) | Err(_) => None,
+        Ok(value) => Some(value),
+    }
+}
+
+pub fn get_opt_string(obj: &JsonObj, key: &str) -> Result<Option<String>, JsonError> {
+    match get_opt(obj, key) {
+        Some(JsonValue::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(JsonError::KeyTypeError(key.to_string())),
+        None => Ok(None),
+    }
+}
+
+pub fn get_opt_bool(obj: &JsonObj, key: &str) -> Result<Option<bool>, JsonError> {
+    match get_opt(obj, key) {
+        Some(JsonValue::Boolean(b)) => Ok(Some(*b)),
+        Some(_) => Err(JsonError::KeyTypeError(key.to_string())),
+        None => Ok(None),
+    }
+}
+
+pub fn get_opt_num(obj: &JsonObj, key: &str) -> Result<Option<f64>, JsonError> {
+    match get_opt(obj, key) {
+        Some(JsonValue::Number(n)) => Ok(Some(*n)),
+        Some(_) => Err(JsonError::KeyTypeError(key.to_string())),
+        None => Ok(None),
+    }
+}
+
+pub fn get_opt_obj<'a>(obj: &'a JsonObj, key: &str) -> Result<Option<&'a JsonObj>, JsonError> {
+    match get_opt(obj, key) {
+        Some(JsonValue::Object(o)) => Ok(Some(o)),
+        Some(_) => Err(JsonError::KeyTypeError(key.to_string())),
+        None => Ok(None),
+    }
+}
+
+pub fn get_bool(obj: &JsonObj, key: &str) -> Result<bool, JsonError> {
+    match get(obj, key)? {
+        JsonValue::Boolean(b) => Ok(*b),
+        _ => Err(JsonError::KeyTypeError(key.to_string())),
+    }
+}
+
+pub fn get_num(obj: &JsonObj, key: &str) -> Result<f64, JsonError> {
+    match get(obj, key)? {
+        JsonValue::Number(n) => Ok(*n),
+        _ => Err(JsonError::KeyTypeError(key.to_string())),
+    }
+}
+
+pub fn get_obj<'a>(obj: &'a JsonObj, key: &str) -> Result<&'a JsonObj, JsonError> {
+    match get(obj, key)? {
+        JsonValue::Object(o) => Ok(o),
+        _ => Err(JsonError::KeyTypeError(key.to_string())),
+    }
+}
+
+pub fn get_list<'a>(obj: &'a JsonObj, key: &str) -> Result<&'a Vec<JsonValue>, JsonError> {
+    match get(obj, key)? {
+        JsonValue::List(l) => Ok(l),
+        _ => Err(JsonError::KeyTypeError(key.to_string())),
+    }
+}
+
+pub fn get_string_list(obj: &JsonObj, key: &str) -> Result<Vec<String>, JsonError> {
+    get_list(obj, key)?
+        .iter()
+        .map(|value| match value {
+            JsonValue::String(s) => Ok(s.clone()),
+            _ => Err(JsonError::KeyTypeError(key.to_string())),
+        })
+        .collect()
+}
+
+// Parses any json value. Unlike stojson this also handles top level lists that hold strings
+// rather than objects, eg. the GET_MARKS reply
+pub fn parse(input: &str) -> Result<JsonValue, JsonError> {
+    Parser { input, pos: 0 }.value()
+}
+
+// Strings are stored with their escapes as they appear in the json, this decodes them for
+// output that is not json itself. Broken escapes are kept as they are.
+pub fn unescape(input: &str) -> String {
+    let mut result = String::new();
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('b') => result.push('\u{8}'),
+            Some('f') => result.push('\u{c}'),
+            Some('u') => {
+                let rest = chars.as_str();
+                match decode_unicode_escape(rest) {
+                    Some((c, used)) => {
+                        result.push(c);
+                        chars = rest[used..].chars();
+                    }
+                    None => result.push_str("\\u"),
+                }
+            }
+            // \" \\ and \/ stand for themselves
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+// The character of a \uXXXX escape with the \u already taken off, and how many bytes it used.
+// Characters outside the BMP are written as a surrogate pair, \uD83D\uDE00
+fn decode_unicode_escape(input: &str) -> Option<(char, usize)> {
+    let hex = |s: &str| u32::from_str_radix(s.get(..4)?, 16).ok();
+    let high = hex(input)?;
+    if !(0xd800..0xdc00).contains(&high) {
+        return char::from_u32(high).map(|c| (c, 4));
+    }
+    let low = hex(input.get(4..)?.strip_prefix("\\u")?)?;
+    if !(0xdc00..0xe000).contains(&low) {
+        return None;
+    }
+    char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)).map(|c| (c, 10))
+}
+
+// The other way around, for text that goes into a json string
+pub fn escape(input: &str) -> String {
+    let mut escaped = String::new();
+    for c in input.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 // !!! TODO json lists might just be arrays of values
 // TODO replace all input[] with get to stop panicking
 pub fn stojson_list(input: Rc<RefCell<String>>) -> Result<JsonEntry, JsonError> {
@@ -111,18 +285,18 @@ pub fn stojson(input: Rc<RefCell<String>>) -> Result<JsonEntry, JsonError> {
     let first_input_char: u8;
     {
         let input_borrow = input.borrow();
-        if let Some(c) = input_borrow.as_bytes().get(0) {
-            first_input_char = c.clone();
+        if let Some(c) = input_borrow.as_bytes().first() {
+            first_input_char = *c;
         } else {
             return Err(JsonError::RanOutOfCharsError);
         };
     }
     match first_input_char {
         b' ' | b'\t' | b'\n' | b'\r' => {
-            {
-                let mut input_borrow = input.borrow_mut();
-                *input_borrow = input_borrow.as_str()[1..].to_string();
-            }
+            with_parser(&input, |parser| {
+                parser.skip_whitespace();
+                Ok(())
+            })?;
             Ok(stojson(Rc::clone(&input))?)
         }
         b'{' => {
@@ -142,104 +316,233 @@ pub fn stojson(input: Rc<RefCell<String>>) -> Result<JsonEntry, JsonError> {
     }
 }
 
-// takes a potential json list with [ peeled off (the object should look like '..]')
-fn handle_json_list(input: Rc<RefCell<String>>) -> Result<Vec<JsonValue>, JsonError> {
-    let first_input_char: u8;
-    {
-        let input_borrow = input.borrow();
-        if let Some(c) = input_borrow.as_bytes().get(0) {
-            first_input_char = c.clone();
-        } else {
-            return Err(JsonError::RanOutOfCharsError);
-        };
+// Holds the input and how far into it parsing got, so values are read in place rather than by
+// copying what is left of the input after every step
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+// A list or object that is still being parsed, along with the key of the object entry whose
+// value comes next. Keeping these on a stack rather than recursing lets deeply nested input
+// like GET_TREE through without running out of stack
+enum Container {
+    List(Vec<JsonValue>),
+    Object(JsonObj, String),
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
     }
-    let mut result: Vec<JsonValue> = vec![];
-    // TODO get vector of results.
-    match first_input_char {
-        b' ' | b'\t' | b'\n' | b'\r' | b',' => {
-            {
-                let mut input_borrow = input.borrow_mut();
-                *input_borrow = input_borrow.as_str()[1..].to_string();
-            }
-            let output = &mut handle_json_list(Rc::clone(&input))?;
-            result.append(output);
-            return Ok(result);
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
         }
-        b']' => {
-            {
-                let mut input_borrow = input.borrow_mut();
-                *input_borrow = input_borrow.as_str()[1..].to_string();
-            }
-            return Ok(result);
+    }
+
+    // Parses the value that starts here, after any whitespace
+    fn value(&mut self) -> Result<JsonValue, JsonError> {
+        let mut stack: Vec<Container> = vec![];
+        let value = self.open_or_scalar(&mut stack)?;
+        self.finish(stack, value)
+    }
+
+    // Parses the entries of an object with { peeled off (the object should look like '..}')
+    fn object(&mut self) -> Result<JsonObj, JsonError> {
+        match self.finish(vec![Container::Object(vec![], String::new())], None)? {
+            JsonValue::Object(obj) => Ok(obj),
+            _ => Err(JsonError::InvalidTypeError),
         }
-        _ => result.push(handle_json_value(Rc::clone(&input))?),
     }
-    if input.borrow().as_bytes().len() > 1 {
-        result.append(&mut handle_json_list(Rc::clone(&input))?);
+
+    // creates a key:value pair from 'k" .. : .. v'
+    fn kvpair(&mut self) -> Result<JsonKVPair, JsonError> {
+        let key = self.key()?;
+        Ok(JsonKVPair {
+            key,
+            value: self.value()?,
+        })
     }
-    return Ok(result);
-}
 
-// takes in a potential json object with { peeled off (the object should look like '..}' ) creates a list of key:value pairs
-fn handle_json_obj(input: Rc<RefCell<String>>) -> Result<JsonObj, JsonError> {
-    let first_input_char: u8;
-    {
-        let input_borrow = input.borrow();
-        if let Some(c) = input_borrow.as_bytes().get(0) {
-            first_input_char = c.clone();
-        } else {
+    // Reads the key of an object entry with the opening " peeled off, up to and including the :
+    fn key(&mut self) -> Result<String, JsonError> {
+        let key_end = handle_json_string(self.rest().as_bytes())?;
+        let key = self.rest()[..key_end - 1].to_string();
+        self.pos += key_end;
+        let separator = find_value_start(self.rest())?;
+        if self.rest().as_bytes().get(separator) != Some(&b':') {
             return Err(JsonError::RanOutOfCharsError);
-        };
+        }
+        self.pos += separator + 1;
+        Ok(key)
     }
-    {
-        let mut input_borrow = input.borrow_mut();
-        *input_borrow = input_borrow.as_str()[1..].to_string();
+
+    // Parses the value that starts here, or if it is a list or object, opens it on the stack
+    fn open_or_scalar(
+        &mut self,
+        stack: &mut Vec<Container>,
+    ) -> Result<Option<JsonValue>, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => stack.push(Container::Object(vec![], String::new())),
+            Some(b'[') => stack.push(Container::List(vec![])),
+            _ => return Ok(Some(self.scalar()?)),
+        }
+        self.pos += 1;
+        Ok(None)
     }
-    let mut result: JsonObj = vec![];
-    match first_input_char {
-        b' ' | b'\t' | b'\n' | b'\r' | b',' => {
-            let output = &mut handle_json_obj(Rc::clone(&input))?;
-            result.append(output);
-            return Ok(result);
+
+    // Adds values to the containers on the stack until they are all closed, value is one that
+    // was just parsed and still has to be added
+    fn finish(
+        &mut self,
+        mut stack: Vec<Container>,
+        mut value: Option<JsonValue>,
+    ) -> Result<JsonValue, JsonError> {
+        loop {
+            if let Some(value) = value.take() {
+                match stack.last_mut() {
+                    None => return Ok(value),
+                    Some(Container::List(list)) => list.push(value),
+                    Some(Container::Object(obj, key)) => obj.push(JsonKVPair {
+                        key: std::mem::take(key),
+                        value,
+                    }),
+                }
+            }
+            let closed = match stack.last_mut() {
+                Some(Container::List(_)) => self.next_list_item()?,
+                Some(Container::Object(_, key)) => match self.next_key()? {
+                    Some(next_key) => {
+                        *key = next_key;
+                        false
+                    }
+                    None => true,
+                },
+                None => return Err(JsonError::RanOutOfCharsError),
+            };
+            if closed {
+                value = match stack.pop() {
+                    Some(Container::List(list)) => Some(JsonValue::List(list)),
+                    Some(Container::Object(obj, _)) => Some(JsonValue::Object(obj)),
+                    None => None,
+                };
+            } else {
+                value = self.open_or_scalar(&mut stack)?;
+            }
         }
-        b'"' => result.push(handle_json_kvpair(Rc::clone(&input))?),
-        b'}' => return Ok(result),
-        _ => return Err(JsonError::InvalidTypeError),
     }
-    if input.borrow().as_bytes().len() > 1 {
-        result.append(&mut handle_json_obj(Rc::clone(&input))?);
+
+    // Skips to the next value of a list, returns true if the list ended instead
+    fn next_list_item(&mut self) -> Result<bool, JsonError> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r' | b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(true);
+                }
+                Some(_) => return Ok(false),
+                None => return Err(JsonError::RanOutOfCharsError),
+            }
+        }
     }
-    return Ok(result);
+
+    // Skips to the next key of an object and reads it, returns None if the object ended instead
+    fn next_key(&mut self) -> Result<Option<String>, JsonError> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r' | b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(None);
+                }
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(Some(self.key()?));
+                }
+                Some(_) => return Err(JsonError::InvalidTypeError),
+                None => return Err(JsonError::RanOutOfCharsError),
+            }
+        }
+    }
+
+    // Parses a string, number, boolean or null
+    fn scalar(&mut self) -> Result<JsonValue, JsonError> {
+        match self.peek() {
+            Some(b'"') => {
+                self.pos += 1;
+                let end_val = handle_json_string(self.rest().as_bytes())?;
+                let string_value = self.rest()[..end_val - 1].to_string();
+                self.pos += end_val;
+                Ok(JsonValue::String(string_value))
+            }
+            Some(b'n' | b't') => {
+                let next_input_chars =
+                    self.rest().get(0..4).ok_or(JsonError::RanOutOfCharsError)?;
+                self.pos += 4;
+                match next_input_chars {
+                    "null" => Ok(JsonValue::Null),
+                    "true" => Ok(JsonValue::Boolean(true)),
+                    _ => Err(JsonError::InvalidTypeError),
+                }
+            }
+            Some(b'f') => {
+                let next_input_chars =
+                    self.rest().get(0..5).ok_or(JsonError::RanOutOfCharsError)?;
+                match next_input_chars {
+                    "false" => {
+                        self.pos += 5;
+                        Ok(JsonValue::Boolean(false))
+                    }
+                    _ => Err(JsonError::InvalidTypeError),
+                }
+            }
+            Some(_) => {
+                // TODO exponents might be allowed ie. 1e10.
+                let num_end =
+                    handle_json_num(self.rest().get(1..).ok_or(JsonError::InvalidTypeError)?)? + 1;
+                let num_value = self.rest()[..num_end]
+                    .parse::<f64>()
+                    .map_err(|_| JsonError::InvalidNumberError)?;
+                self.pos += num_end;
+                Ok(JsonValue::Number(num_value))
+            }
+            None => Err(JsonError::RanOutOfCharsError),
+        }
+    }
+}
+
+// Runs the parser over the input and leaves in it whatever the parser did not consume
+fn with_parser<T>(
+    input: &Rc<RefCell<String>>,
+    parse: impl FnOnce(&mut Parser) -> Result<T, JsonError>,
+) -> Result<T, JsonError> {
+    let mut input_borrow = input.borrow_mut();
+    let mut parser = Parser {
+        input: &input_borrow,
+        pos: 0,
+    };
+    let result = parse(&mut parser);
+    let consumed = parser.pos;
+    input_borrow.drain(..consumed);
+    result
+}
+
+// takes in a potential json object with { peeled off (the object should look like '..}' ) creates a list of key:value pairs
+fn handle_json_obj(input: Rc<RefCell<String>>) -> Result<JsonObj, JsonError> {
+    with_parser(&input, |parser| parser.object())
 }
 
 // creates a key:value pair from 'k" .. : .. v'
 fn handle_json_kvpair(input: Rc<RefCell<String>>) -> Result<JsonKVPair, JsonError> {
-    let key_end: usize;
-    let mut result: JsonKVPair = JsonKVPair {
-        key: String::new(),
-        value: JsonValue::None,
-    };
-    {
-        let input_borrow = input.borrow();
-        key_end = handle_json_string(&input_borrow.as_bytes())? - 1;
-        result.key.push_str(&input_borrow[..key_end]);
-    }
-    // key_end is the ending index, but we want to remove that and the :
-    {
-        let mut input_borrow = input.borrow_mut();
-        *input_borrow = input_borrow[key_end + 1..].to_string();
-    }
-    let val_start: usize;
-    {
-        let input_borrow = input.borrow();
-        val_start = find_value_start(&input_borrow)? + 1;
-    }
-    {
-        let mut input_borrow = input.borrow_mut();
-        *input_borrow = input_borrow[val_start..].to_string();
-    }
-    result.value = handle_json_value(Rc::clone(&input))?;
-    return Ok(result);
+    with_parser(&input, |parser| parser.kvpair())
 }
 
 // Returns the index of the : separator
@@ -253,146 +556,44 @@ fn find_value_start(input: &str) -> Result<usize, JsonError> {
         }
         result += 1;
     }
-    return Ok(result);
+    Ok(result)
 }
 
 // returns the first index after the last " is in the original json string ".."
 fn handle_json_string(input: &[u8]) -> Result<usize, JsonError> {
-    return match input.get(0) {
-        Some(b) => match b {
-            b'\\' => match input.get(2) {
-                Some(_c) => Ok(2 + handle_json_string(&input[2..])?),
-                None => Err(JsonError::RanOutOfCharsError),
+    let mut index = 0;
+    loop {
+        match input.get(index) {
+            Some(b'\\') => match input.get(index + 2) {
+                Some(_c) => index += 2,
+                None => return Err(JsonError::RanOutOfCharsError),
             },
-            b'"' => Ok(1),
-            _ => Ok(1 + handle_json_string(&input[1..])?),
-        },
-        None => Err(JsonError::RanOutOfCharsError),
-    };
+            Some(b'"') => return Ok(index + 1),
+            Some(_) => index += 1,
+            None => return Err(JsonError::RanOutOfCharsError),
+        }
+    }
 }
 
 // handles values in the format wv.. where w is any whitespace, v is the value and any remaining
-// json strings that occur after
+// json strings that occur after. parse covers this outside of the tests
+#[cfg(test)]
 fn handle_json_value(input: Rc<RefCell<String>>) -> Result<JsonValue, JsonError> {
-    let first_input_char: u8;
-    {
-        let input_borrow = input.borrow();
-        if let Some(c) = input_borrow.as_bytes().get(0) {
-            first_input_char = c.clone();
-        } else {
-            return Err(JsonError::RanOutOfCharsError);
-        };
-    }
-    return match first_input_char {
-        b'"' => {
-            // turn json string into a JsonValue
-            let mut string_value: String = String::new();
-            {
-                let mut input_borrow = input.borrow_mut();
-                *input_borrow = input_borrow[1..].to_string();
-                // PRetty sure need to clone this TODO
-                let end_val = handle_json_string(&input_borrow.as_bytes())? - 1;
-                let input_slice = &input_borrow[..end_val];
-                string_value.push_str(input_slice);
-                *input_borrow = input_borrow[end_val + 1..].to_string();
-            }
-            Ok(JsonValue::String(string_value))
-        }
-        b'n' | b't' => {
-            let next_input_chars: String;
-            {
-                let input_borrow = input.borrow();
-                if let Some(str) = input_borrow.get(0..4) {
-                    next_input_chars = str.to_string();
-                } else {
-                    return Err(JsonError::RanOutOfCharsError);
-                };
-            }
-            {
-                let mut input_borrow = input.borrow_mut();
-                *input_borrow = input_borrow[4..].to_string();
-            }
-            return match next_input_chars.as_str() {
-                //handle null
-                "null" => Ok(JsonValue::Null),
-                "true" => Ok(JsonValue::Boolean(true)),
-                _ => Err(JsonError::InvalidTypeError),
-            };
-        }
-        b'f' => {
-            let next_input_chars: String;
-            {
-                let input_borrow = input.borrow();
-                if let Some(c) = input_borrow.as_str().get(0..5) {
-                    next_input_chars = c.to_string();
-                } else {
-                    return Err(JsonError::RanOutOfCharsError);
-                };
-            }
-            //handle false
-            return match next_input_chars.as_str() {
-                "false" => {
-                    {
-                        let mut input_borrow = input.borrow_mut();
-                        *input_borrow = input_borrow[5..].to_string();
-                    }
-                    Ok(JsonValue::Boolean(false))
-                }
-                _ => Err(JsonError::InvalidTypeError),
-            };
-        }
-        b' ' | b'\t' | b'\n' | b'\r' => {
-            {
-                let mut input_borrow = input.borrow_mut();
-                *input_borrow = input_borrow[1..].to_string();
-            }
-            Ok(handle_json_value(Rc::clone(&input))?)
-        }
-        b'{' => {
-            {
-                let mut input_borrow = input.borrow_mut();
-                *input_borrow = input_borrow[1..].to_string();
-            }
-            Ok(JsonValue::Object(handle_json_obj(Rc::clone(&input))?))
-        }
-        b'[' => {
-            {
-                let mut input_borrow = input.borrow_mut();
-                *input_borrow = input_borrow[1..].to_string();
-            }
-            Ok(JsonValue::List(handle_json_list(Rc::clone(&input))?))
-        }
-        _ => {
-            // TODO exponents might be allowed ie. 1e10.
-            let num_value: f64;
-            let num_end: usize;
-            {
-                let input_borrow = input.borrow();
-                num_end = handle_json_num(&input_borrow[1..])? + 1;
-                if let Ok(n) = input_borrow[0..num_end].parse::<f64>() {
-                    num_value = n;
-                } else {
-                    return Err(JsonError::InvalidNumberError);
-                }
-            }
-            let mut input_borrow = input.borrow_mut();
-            *input_borrow = input_borrow[num_end..].to_string();
-            Ok(JsonValue::Number(num_value))
-        }
-    };
+    with_parser(&input, |parser| parser.value())
 }
 
 // Returns index of possible end of number value (not inclusive)
 fn handle_json_num(input: &str) -> Result<usize, JsonError> {
     // loop forward until a whitespace, tab, newline, return, or any environment closing
-    return match input.as_bytes().get(0) {
-        Some(b) => match b {
-            b'0'..b':' | b'.' => Ok(1 + handle_json_num(&input[1..])?),
-            b',' | b'\t' | b'\r' | b'\n' | b' ' | b'}' | b']' => Ok(0),
-            _ => Err(JsonError::InvalidTypeError),
-        },
-        None => Err(JsonError::RanOutOfCharsError),
-    };
+    let mut index = 0;
+    loop {
+        match input.as_bytes().get(index) {
+            Some(b'0'..b':' | b'.') => index += 1,
+            Some(b',' | b'\t' | b'\r' | b'\n' | b' ' | b'}' | b']') => return Ok(index),
+            Some(_) => return Err(JsonError::InvalidTypeError),
+            None => return Err(JsonError::RanOutOfCharsError),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -569,6 +770,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn handle_json_kv_pair_functions() {
         let basic_string = Rc::new(RefCell::new(String::from("key\":\"value\"")));
         let basic_int = Rc::new(RefCell::new(String::from("int\":7}")));
@@ -955,4 +1157,68 @@ mod test {
             panic!("ruh roh was not an object!");
         }
     }
+
+    #[test]
+    fn parse_handles_top_level_string_lists() {
+        if let JsonValue::List(values) = parse("[ \"default\", \"resize\" ]").unwrap() {
+            assert_eq!(values.len(), 2);
+            assert!(matches!(&values[1], JsonValue::String(s) if s == "resize"));
+        } else {
+            panic!("should have been a list");
+        }
+    }
+
+    #[test]
+    fn get_helpers_look_up_by_key() {
+        let obj = match parse("{\"name\":\"1\",\"num\":1,\"urgent\":false,\"rep\":null}").unwrap() {
+            JsonValue::Object(obj) => obj,
+            _ => panic!("should have been an object"),
+        };
+        assert_eq!(get_string(&obj, "name").unwrap(), "1");
+        assert_eq!(get_num(&obj, "num").unwrap(), 1f64);
+        assert!(!get_bool(&obj, "urgent").unwrap());
+        assert_eq!(get_opt_string(&obj, "rep").unwrap(), None);
+        assert_eq!(get_opt_string(&obj, "absent").unwrap(), None);
+        assert!(matches!(
+            get_string(&obj, "absent").unwrap_err(),
+            JsonError::MissingKeyError(key) if key == "absent"
+        ));
+        assert!(matches!(
+            get_bool(&obj, "name").unwrap_err(),
+            JsonError::KeyTypeError(key) if key == "name"
+        ));
+    }
+
+    #[test]
+    fn unescape_decodes_what_escape_encodes() {
+        assert_eq!(unescape("web\\/dev"), "web/dev");
+        assert_eq!(unescape("caf\\u00e9 \\\"x\\\" \\\\"), "café \"x\" \\");
+        assert_eq!(unescape("\\ud83d\\ude00\\n"), "😀\n");
+        // broken escapes are left alone
+        assert_eq!(unescape("\\u12"), "\\u12");
+        assert_eq!(unescape("\\ud83d"), "\\ud83d");
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\n");
+        assert_eq!(unescape(&escape("a\"b\\c\u{1}")), "a\"b\\c\u{1}");
+    }
+
+    #[test]
+    fn parse_handles_deep_nesting() {
+        let depth = 100_000;
+        let input = format!(
+            "{}null{}",
+            "[{\"nodes\": ".repeat(depth),
+            "}]".repeat(depth)
+        );
+        let mut value = parse(&input).unwrap();
+        for _ in 0..depth {
+            value = match value {
+                JsonValue::List(mut list) => match list.pop() {
+                    Some(JsonValue::Object(mut obj)) => obj.pop().unwrap().value,
+                    _ => panic!("should have held an object"),
+                },
+                _ => panic!("should have been a list"),
+            };
+        }
+        assert!(matches!(value, JsonValue::Null));
+    }
 }
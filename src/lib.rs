@@ -1,20 +1,37 @@
-use std::sync::{Arc, Mutex};
-
+pub mod cli;
+pub mod daemon;
+pub mod formatter;
+pub mod icons;
 pub mod ipc_client;
+pub mod ipc_types;
 pub mod json_parser;
+pub mod listener;
+pub mod state;
+pub mod template;
 
-pub fn run(args: &Vec<String>) {
-    if args.len() < 2 {
-        ipc_client::run_ipc();
-    }
-    match args[1].as_str() {
-        "get-workspaces" => {
-            let fd = ipc_client::connect().unwrap();
-            let fd_mutex = Arc::new(Mutex::new(fd));
-            let workspace_config = ipc_client::get_workspaces_summary(Arc::clone(&fd_mutex));
+pub fn run(args: &[String]) -> Result<(), ipc_client::IPCError> {
+    let options = cli::parse_args(args)?;
+    match &options.command {
+        cli::Command::ListenWorkspaces => {
+            listener::run_listener(&mut listener::WorkspaceListener::new(&options), &options)
+        }
+        cli::Command::GetWorkspaces => {
+            let (client, _) = options.connect()?;
+            let workspace_config = ipc_client::get_workspaces_summary(&client, &options.view)?;
             println!("{}", workspace_config);
+            Ok(())
+        }
+        cli::Command::WindowTitle => {
+            listener::run_listener(&mut listener::WindowTitleListener::default(), &options)
+        }
+        cli::Command::Mode => {
+            listener::run_listener(&mut listener::ModeListener::default(), &options)
         }
-        _ => {}
+        cli::Command::Input(identifier) => listener::run_listener(
+            &mut listener::InputListener::new(identifier.clone()),
+            &options,
+        ),
+        cli::Command::Daemon => daemon::run_daemon(&options),
     }
 }
 
@@ -0,0 +1,772 @@
+use std::io::{self, Write};
+use std::net::Shutdown;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cli::Options;
+use crate::formatter::{self, WorkspaceView};
+use crate::ipc_client::{self, EventStream, IPCClient, IPCError, IPCEvents, Subscription};
+use crate::ipc_types::{Event, Input, Node, WorkspaceChange, WorkspaceEvent};
+use crate::state::State;
+
+// A listen mode for eww's deflisten: the events it needs and how to turn them into output lines.
+// Events arrive on their own connection, queries go through the client passed in.
+pub trait Listener {
+    fn events(&self) -> Vec<IPCEvents>;
+
+    // Called once after subscribing, returns the line describing the current state
+    fn init(&mut self, client: &IPCClient) -> Result<Option<String>, IPCError>;
+
+    // Returns a line to print if the event changed what should be shown
+    fn handle(&mut self, client: &IPCClient, event: Event) -> Result<Option<String>, IPCError>;
+}
+
+// Runs the listener until sway cannot be reached more than options.max_retries times in a row,
+// reconnecting with a growing delay whenever the socket drops or sway announces that it is
+// shutting down. The disconnected line is printed once per outage.
+pub fn run_listener<L: Listener>(listener: &mut L, options: &Options) -> Result<(), IPCError> {
+    let mut emitter = Emitter::new(io::stdout(), options.always_emit);
+    let mut failures: u32 = 0;
+    let mut reported = false;
+    loop {
+        let mut connected = false;
+        // Ok means sway sent a shutdown event, it may be restarting so it is retried as well
+        let result = listen(listener, options, &mut emitter, &mut connected);
+        // eg. the input mode on i3 or eww closing stdout, reconnecting will not help
+        if let Err(IPCError::UnsupportedError(_) | IPCError::OutputError(_)) = result {
+            return result;
+        }
+        if connected {
+            failures = 0;
+            reported = false;
+        }
+        if !reported {
+            emitter.emit(&options.disconnected_line)?;
+            reported = true;
+        }
+        if let Err(e) = result {
+            if !connected {
+                failures += 1;
+            }
+            if options.max_retries.is_some_and(|max| failures > max) {
+                return Err(e);
+            }
+        }
+        thread::sleep(backoff(failures));
+    }
+}
+
+// 250ms doubling on every failed attempt, capped at 8s
+pub(crate) fn backoff(failures: u32) -> Duration {
+    Duration::from_millis(250 << failures.min(5))
+}
+
+// Connects both connections, prints the current state and then every update until the event
+// connection fails or sway shuts down
+fn listen<L: Listener, W: Write>(
+    listener: &mut L,
+    options: &Options,
+    emitter: &mut Emitter<W>,
+    connected: &mut bool,
+) -> Result<(), IPCError> {
+    let (client, path) = options.connect()?;
+    let events = Subscription::new()
+        .max_payload_len(options.max_payload_len)
+        .wm(client.window_manager())
+        .events(&listener.events())
+        .event(IPCEvents::Shutdown)
+        .subscribe_on(ipc_client::connect_to(&path)?)?;
+    *connected = true;
+    let event_stream = events.try_clone_stream()?;
+    let (sender, receiver) = mpsc::channel();
+    let verbose = options.verbose;
+    thread::spawn(move || read_events(events, sender, verbose));
+    let result = print_lines(
+        listener,
+        &client,
+        &receiver,
+        options.debounce,
+        options.debounce_max,
+        emitter,
+    );
+    // unblocks the reader thread if printing stopped first
+    let _ = event_stream.shutdown(Shutdown::Both);
+    result
+}
+
+// Reads events on their own thread so bursts can be collected with a timeout. An event that
+// cannot be decoded, eg. of a type added in a newer sway, is skipped as the message was read
+// whole and the stream is still in sync. Only a broken connection or a shutdown ends it.
+fn read_events(events: EventStream, sender: Sender<Result<Event, IPCError>>, verbose: bool) {
+    loop {
+        let event = match events.next_event() {
+            Err(e @ (IPCError::UnknownEventError(_) | IPCError::JsonError(_))) => {
+                if verbose {
+                    eprintln!("skipping event: {e}");
+                }
+                continue;
+            }
+            event => event,
+        };
+        let last = !matches!(event, Ok(ref event) if !matches!(event, Event::Shutdown(_)));
+        if sender.send(event).is_err() || last {
+            return;
+        }
+    }
+}
+
+// Writes the lines of a single eww variable, skipping any line that is identical to the one
+// printed before it as eww would only re-render the same thing
+pub struct Emitter<W: Write> {
+    out: W,
+    last_line: Option<String>,
+    always_emit: bool,
+}
+
+impl<W: Write> Emitter<W> {
+    pub fn new(out: W, always_emit: bool) -> Emitter<W> {
+        Emitter {
+            out,
+            last_line: None,
+            always_emit,
+        }
+    }
+
+    pub fn emit(&mut self, line: &str) -> Result<(), IPCError> {
+        if !self.always_emit && self.last_line.as_deref() == Some(line) {
+            return Ok(());
+        }
+        writeln!(self.out, "{line}").map_err(IPCError::OutputError)?;
+        self.last_line = Some(line.to_string());
+        Ok(())
+    }
+}
+
+// Prints the line from init and then the lines for each event. With a debounce events are
+// handled until none arrived for that long, and only the final line is printed. debounce_max
+// bounds how long a burst that keeps going can hold the line back.
+fn print_lines<L: Listener, W: Write>(
+    listener: &mut L,
+    client: &IPCClient,
+    receiver: &Receiver<Result<Event, IPCError>>,
+    debounce: Duration,
+    debounce_max: Option<Duration>,
+    emitter: &mut Emitter<W>,
+) -> Result<(), IPCError> {
+    if let Some(line) = listener.init(client)? {
+        emitter.emit(&line)?;
+    }
+    loop {
+        let mut line = match receiver.recv().map_err(|_| IPCError::UnexpectedEof)?? {
+            Event::Shutdown(_) => return Ok(()),
+            event => listener.handle(client, event)?,
+        };
+        if debounce.is_zero() {
+            if let Some(line) = line {
+                emitter.emit(&line)?;
+            }
+            continue;
+        }
+        let started = Instant::now();
+        let mut deadline = started + debounce;
+        loop {
+            if let Some(max) = debounce_max {
+                deadline = deadline.min(started + max);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let event = match receiver.recv_timeout(remaining) {
+                Ok(event) => event?,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Err(IPCError::UnexpectedEof),
+            };
+            match event {
+                Event::Shutdown(_) => return Ok(()),
+                event => {
+                    if let Some(next) = listener.handle(client, event)? {
+                        line = Some(next);
+                    }
+                    deadline = Instant::now() + debounce;
+                }
+            }
+        }
+        if let Some(line) = line {
+            emitter.emit(&line)?;
+        }
+    }
+}
+
+// Prints the workspace list whenever a workspace change could have altered it. The list is
+// patched from the events themselves and only fetched again when an event cannot be applied or
+// the last full fetch is older than resync_interval. With --icons windows opening, closing and
+// moving also fetch the tree for the apps on each workspace.
+#[derive(Debug)]
+pub struct WorkspaceListener {
+    state: Option<State>,
+    resync_interval: Duration,
+    view: WorkspaceView,
+}
+
+impl WorkspaceListener {
+    pub fn new(options: &Options) -> WorkspaceListener {
+        WorkspaceListener {
+            state: None,
+            resync_interval: options.resync_interval,
+            view: options.view.clone(),
+        }
+    }
+
+    fn render(&self, state: &State) -> String {
+        self.view.render(state.workspaces())
+    }
+
+    fn resync(&mut self, client: &IPCClient) -> Result<Option<String>, IPCError> {
+        self.view.set_wm(client.window_manager());
+        let mut state = State::new(client.get_workspaces()?);
+        if self.view.icons.is_some() {
+            state.update_apps(&client.get_tree()?);
+        }
+        let line = self.render(&state);
+        self.state = Some(state);
+        Ok(Some(line))
+    }
+
+    fn handle_workspace(
+        &mut self,
+        client: &IPCClient,
+        event: &WorkspaceEvent,
+    ) -> Result<Option<String>, IPCError> {
+        if let WorkspaceChange::Unknown(_) = event.change {
+            return Ok(None);
+        }
+        // every other change can alter which workspaces exist, their names, outputs or flags
+        if let Some(mut state) = self.state.take() {
+            if state.age() < self.resync_interval && state.apply(event) {
+                let line = self.render(&state);
+                self.state = Some(state);
+                return Ok(Some(line));
+            }
+        }
+        self.resync(client)
+    }
+
+    // Windows opening, closing or moving change the apps of a workspace, nothing else about it
+    fn update_apps(&mut self, client: &IPCClient) -> Result<Option<String>, IPCError> {
+        match self.state.take() {
+            Some(mut state) if state.age() < self.resync_interval => {
+                state.update_apps(&client.get_tree()?);
+                let line = self.render(&state);
+                self.state = Some(state);
+                Ok(Some(line))
+            }
+            _ => self.resync(client),
+        }
+    }
+}
+
+impl Listener for WorkspaceListener {
+    fn events(&self) -> Vec<IPCEvents> {
+        if self.view.icons.is_some() {
+            vec![IPCEvents::Workspace, IPCEvents::Window]
+        } else {
+            vec![IPCEvents::Workspace]
+        }
+    }
+
+    fn init(&mut self, client: &IPCClient) -> Result<Option<String>, IPCError> {
+        self.resync(client)
+    }
+
+    fn handle(&mut self, client: &IPCClient, event: Event) -> Result<Option<String>, IPCError> {
+        match event {
+            Event::Workspace(event) => self.handle_workspace(client, &event),
+            Event::Window(event) if self.view.icons.is_some() => match event.change.as_str() {
+                "new" | "close" | "move" => self.update_apps(client),
+                _ => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+}
+
+// Prints the focused window as a json object, or {} once no window has focus
+#[derive(Debug, Default)]
+pub struct WindowTitleListener {
+    focused_id: Option<i64>,
+}
+
+impl WindowTitleListener {
+    fn show(&mut self, window: Option<&Node>) -> Option<String> {
+        self.focused_id = window.filter(|node| node.is_window()).map(|node| node.id);
+        Some(formatter::window_json(window))
+    }
+}
+
+impl Listener for WindowTitleListener {
+    fn events(&self) -> Vec<IPCEvents> {
+        // workspace focus is needed to notice switching to an empty workspace
+        vec![IPCEvents::Window, IPCEvents::Workspace]
+    }
+
+    fn init(&mut self, client: &IPCClient) -> Result<Option<String>, IPCError> {
+        let tree = client.get_tree()?;
+        Ok(self.show(tree.find_focused()))
+    }
+
+    // Events carry the nodes they are about, so none of them needs another look at the tree
+    fn handle(&mut self, _client: &IPCClient, event: Event) -> Result<Option<String>, IPCError> {
+        match event {
+            Event::Window(event) => match event.change.as_str() {
+                "focus" => Ok(self.show(Some(&event.container))),
+                "title" | "fullscreen_mode" | "floating" | "urgent" | "mark" => {
+                    if self.focused_id == Some(event.container.id) {
+                        Ok(self.show(Some(&event.container)))
+                    } else {
+                        Ok(None)
+                    }
+                }
+                // closing the last window on a workspace does not send a focus event, and
+                // closing any other one sends it for the window that gets focus next
+                "close" if self.focused_id == Some(event.container.id) => Ok(self.show(None)),
+                // a window moved away keeps focus only if focus followed it, otherwise the
+                // window taking its place is announced with a focus event
+                "move" if event.container.focused => Ok(self.show(Some(&event.container))),
+                "move" if self.focused_id == Some(event.container.id) => Ok(self.show(None)),
+                _ => Ok(None),
+            },
+            // the workspace comes with its windows, which tell whether one of them has focus
+            Event::Workspace(event) if event.change == WorkspaceChange::Focus => Ok(event
+                .current
+                .as_ref()
+                .and_then(|workspace| self.show(workspace.find_focused()))),
+            _ => Ok(None),
+        }
+    }
+}
+
+// Prints the active binding mode, eg. "default" or "resize"
+#[derive(Debug, Default)]
+pub struct ModeListener {}
+
+impl Listener for ModeListener {
+    fn events(&self) -> Vec<IPCEvents> {
+        vec![IPCEvents::Mode]
+    }
+
+    fn init(&mut self, client: &IPCClient) -> Result<Option<String>, IPCError> {
+        // GET_BINDING_STATE does not say whether the mode uses pango markup
+        let state = client.get_binding_state()?;
+        Ok(Some(formatter::mode_json(&state.name, false)))
+    }
+
+    fn handle(&mut self, _client: &IPCClient, event: Event) -> Result<Option<String>, IPCError> {
+        match event {
+            Event::Mode(mode) => Ok(Some(formatter::mode_json(&mode.change, mode.pango_markup))),
+            _ => Ok(None),
+        }
+    }
+}
+
+// Prints the active keyboard layout, optionally only for the keyboard with the given identifier
+#[derive(Debug, Default)]
+pub struct InputListener {
+    identifier: Option<String>,
+}
+
+impl InputListener {
+    pub fn new(identifier: Option<String>) -> InputListener {
+        InputListener { identifier }
+    }
+
+    fn matches(&self, input: &Input) -> bool {
+        input.input_type == "keyboard"
+            && self
+                .identifier
+                .as_ref()
+                .is_none_or(|identifier| *identifier == input.identifier)
+    }
+}
+
+impl Listener for InputListener {
+    fn events(&self) -> Vec<IPCEvents> {
+        vec![IPCEvents::Input]
+    }
+
+    fn init(&mut self, client: &IPCClient) -> Result<Option<String>, IPCError> {
+        let inputs = client.get_inputs()?;
+        let keyboard = inputs.iter().find(|input| self.matches(input));
+        Ok(Some(formatter::keyboard_layout_json(keyboard)))
+    }
+
+    fn handle(&mut self, _client: &IPCClient, event: Event) -> Result<Option<String>, IPCError> {
+        match event {
+            Event::Input(event)
+                if (event.change == "xkb_layout" || event.change == "xkb_keymap")
+                    && self.matches(&event.input) =>
+            {
+                Ok(Some(formatter::keyboard_layout_json(Some(&event.input))))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipc_client::{IPCFormat, IPCMessages};
+    use crate::ipc_types::fixtures::{self, workspace};
+    use crate::ipc_types::{ShutdownEvent, Workspace};
+    use std::os::unix::net::UnixStream;
+
+    fn window_event(change: &str, id: i64, title: &str) -> Event {
+        let json = format!("{{ \"change\": \"{change}\", \"container\": {{ \"id\": {id}, \"type\": \"con\", \"name\": \"{title}\", \"layout\": \"none\", \"rect\": {{ \"x\": 0, \"y\": 0, \"width\": 960, \"height\": 1080 }}, \"urgent\": false, \"focused\": true, \"marks\": [ ], \"focus\": [ ], \"fullscreen_mode\": 0, \"app_id\": \"foot\", \"pid\": 42, \"nodes\": [ ], \"floating_nodes\": [ ] }} }}");
+        Event::from_json(IPCEvents::Window, &json).unwrap()
+    }
+
+    #[test]
+    fn window_title_follows_focus() {
+        let (fd, _sway_fd) = UnixStream::pair().unwrap();
+        let client = IPCClient::from_stream(fd);
+        let mut listener = WindowTitleListener::default();
+
+        let line = listener
+            .handle(&client, window_event("focus", 7, "~"))
+            .unwrap();
+        assert_eq!(
+            line.as_deref(),
+            Some("{\"title\":\"~\",\"app_id\":\"foot\",\"class\":null,\"pid\":42,\"floating\":false,\"fullscreen\":false}")
+        );
+        // a title change on a window that does not have focus is not shown
+        let line = listener
+            .handle(&client, window_event("title", 8, "vim"))
+            .unwrap();
+        assert_eq!(line, None);
+        let line = listener
+            .handle(&client, window_event("title", 7, "vim"))
+            .unwrap();
+        assert!(line.unwrap().starts_with("{\"title\":\"vim\""));
+    }
+
+    #[test]
+    fn window_title_follows_close_move_and_workspace_focus_without_asking_sway() {
+        // sway is gone, so any query would fail
+        let (fd, sway_fd) = UnixStream::pair().unwrap();
+        drop(sway_fd);
+        let client = IPCClient::from_stream(fd);
+        let mut listener = WindowTitleListener::default();
+
+        listener
+            .handle(&client, window_event("focus", 7, "~"))
+            .unwrap();
+        // moved to another workspace without focus following it
+        let mut moved = window_event("move", 7, "~");
+        if let Event::Window(event) = &mut moved {
+            event.container.focused = false;
+        }
+        assert_eq!(
+            listener.handle(&client, moved).unwrap().as_deref(),
+            Some("{}")
+        );
+
+        // the last window on a workspace was closed
+        listener
+            .handle(&client, window_event("focus", 8, "vim"))
+            .unwrap();
+        assert_eq!(
+            listener
+                .handle(&client, window_event("close", 9, "htop"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            listener
+                .handle(&client, window_event("close", 8, "vim"))
+                .unwrap()
+                .as_deref(),
+            Some("{}")
+        );
+
+        let workspace_focus = |windows: Vec<Node>| {
+            let mut event = fixtures::workspace_event("focus", &workspace("2", false, false));
+            if let Event::Workspace(event) = &mut event {
+                event.current.as_mut().unwrap().nodes = windows;
+            }
+            event
+        };
+        let htop = match window_event("focus", 10, "htop") {
+            Event::Window(event) => event.container,
+            e => panic!("expected a window event, got {e:?}"),
+        };
+        let line = listener
+            .handle(&client, workspace_focus(vec![htop]))
+            .unwrap();
+        assert!(line.unwrap().starts_with("{\"title\":\"htop\""));
+        assert_eq!(
+            listener
+                .handle(&client, workspace_focus(vec![]))
+                .unwrap()
+                .as_deref(),
+            Some("{}")
+        );
+    }
+
+    #[test]
+    fn mode_prints_every_change() {
+        let (fd, _sway_fd) = UnixStream::pair().unwrap();
+        let client = IPCClient::from_stream(fd);
+        let event = Event::from_json(
+            IPCEvents::Mode,
+            "{ \"change\": \"resize\", \"pango_markup\": true }",
+        )
+        .unwrap();
+        assert_eq!(
+            ModeListener::default().handle(&client, event).unwrap(),
+            Some(String::from("{\"name\":\"resize\",\"pango_markup\":true}"))
+        );
+    }
+
+    #[test]
+    fn input_filters_keyboards_by_identifier() {
+        let (fd, _sway_fd) = UnixStream::pair().unwrap();
+        let client = IPCClient::from_stream(fd);
+        let event = |identifier: &str| {
+            let json = format!("{{ \"change\": \"xkb_layout\", \"input\": {{ \"identifier\": \"{identifier}\", \"name\": \"Keyboard\", \"vendor\": 1, \"product\": 1, \"type\": \"keyboard\", \"xkb_layout_names\": [ \"English (US)\", \"German\" ], \"xkb_active_layout_index\": 1, \"xkb_active_layout_name\": \"German\", \"libinput\": {{ \"send_events\": \"enabled\" }} }} }}");
+            Event::from_json(IPCEvents::Input, &json).unwrap()
+        };
+        let mut listener =
+            InputListener::new(Some(String::from("1:1:AT_Translated_Set_2_keyboard")));
+        assert_eq!(
+            listener.handle(&client, event("1:1:AT_Translated_Set_2_keyboard")).unwrap(),
+            Some(String::from("{\"identifier\":\"1:1:AT_Translated_Set_2_keyboard\",\"layout\":\"German\",\"index\":1,\"layouts\":[\"English (US)\",\"German\"]}"))
+        );
+        assert_eq!(
+            listener
+                .handle(&client, event("1133:49970:Logitech_Keyboard"))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn workspace_ignores_unknown_changes() {
+        let (fd, _sway_fd) = UnixStream::pair().unwrap();
+        let client = IPCClient::from_stream(fd);
+        let event = Event::from_json(
+            IPCEvents::Workspace,
+            "{\"change\":\"teleport\",\"current\":null}",
+        )
+        .unwrap();
+        assert_eq!(
+            WorkspaceListener::new(&Options::default())
+                .handle(&client, event)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn workspace_focus_is_patched_without_asking_sway() {
+        // sway is gone, so any query would fail
+        let (fd, sway_fd) = UnixStream::pair().unwrap();
+        drop(sway_fd);
+        let client = IPCClient::from_stream(fd);
+        let workspaces = vec![
+            Workspace {
+                id: 4,
+                ..workspace("1", true, false)
+            },
+            Workspace {
+                id: 5,
+                ..workspace("2", false, false)
+            },
+        ];
+        let mut listener = WorkspaceListener::new(&Options::default());
+        listener.state = Some(State::new(workspaces));
+        let event = fixtures::workspace_event(
+            "focus",
+            &Workspace {
+                id: 5,
+                ..workspace("2", true, false)
+            },
+        );
+        assert_eq!(
+            listener.handle(&client, event.clone()).unwrap().as_deref(),
+            Some("[\"1\",\"2focused\"]")
+        );
+        // once the state is too old the list is fetched again, which fails here
+        listener.resync_interval = Duration::ZERO;
+        assert!(listener.handle(&client, event).is_err());
+    }
+
+    // Prints the name of every mode event, and "default" at startup
+    struct ModeNames {}
+
+    impl Listener for ModeNames {
+        fn events(&self) -> Vec<IPCEvents> {
+            vec![IPCEvents::Mode]
+        }
+
+        fn init(&mut self, _client: &IPCClient) -> Result<Option<String>, IPCError> {
+            Ok(Some(String::from("default")))
+        }
+
+        fn handle(
+            &mut self,
+            _client: &IPCClient,
+            event: Event,
+        ) -> Result<Option<String>, IPCError> {
+            match event {
+                Event::Mode(mode) => Ok(Some(mode.change)),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    fn mode(name: &str) -> Result<Event, IPCError> {
+        let json = format!("{{ \"change\": \"{name}\", \"pango_markup\": false }}");
+        Ok(Event::from_json(IPCEvents::Mode, &json).unwrap())
+    }
+
+    #[test]
+    fn debounce_coalesces_bursts() {
+        let (fd, _sway_fd) = UnixStream::pair().unwrap();
+        let client = IPCClient::from_stream(fd);
+        let (sender, receiver) = mpsc::channel();
+        // a burst that ends where it started is not printed at all
+        for name in ["resize", "default"] {
+            sender.send(mode(name)).unwrap();
+        }
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            for name in ["move", "resize"] {
+                sender.send(mode(name)).unwrap();
+            }
+            thread::sleep(Duration::from_millis(100));
+            sender.send(mode("resize")).unwrap();
+        });
+        let mut out = vec![];
+        let result = print_lines(
+            &mut ModeNames {},
+            &client,
+            &receiver,
+            Duration::from_millis(20),
+            None,
+            &mut Emitter::new(&mut out, false),
+        );
+        assert!(matches!(result, Err(IPCError::UnexpectedEof)));
+        assert_eq!(String::from_utf8(out).unwrap(), "default\nresize\n");
+    }
+
+    // Runs print_lines over a burst of events 5ms apart that lasts about 100ms, longer than the
+    // debounce, and returns what was printed
+    fn print_burst(debounce_max: Option<Duration>) -> String {
+        let (fd, _sway_fd) = UnixStream::pair().unwrap();
+        let client = IPCClient::from_stream(fd);
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for i in 0..20 {
+                sender.send(mode(&i.to_string())).unwrap();
+                thread::sleep(Duration::from_millis(5));
+            }
+            sender.send(mode("resize")).unwrap();
+            thread::sleep(Duration::from_millis(300));
+        });
+        let mut out = vec![];
+        let result = print_lines(
+            &mut ModeNames {},
+            &client,
+            &receiver,
+            Duration::from_millis(50),
+            debounce_max,
+            &mut Emitter::new(&mut out, false),
+        );
+        assert!(matches!(result, Err(IPCError::UnexpectedEof)));
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn debounce_waits_for_the_burst_to_end() {
+        assert_eq!(print_burst(None), "default\nresize\n");
+        // unless it keeps going for longer than the maximum
+        let out = print_burst(Some(Duration::from_millis(30)));
+        assert!(out.lines().count() > 2, "{out}");
+        assert!(out.ends_with("\nresize\n"), "{out}");
+    }
+
+    #[test]
+    fn events_that_cannot_be_decoded_are_skipped() {
+        let (fd, mut sway_fd) = UnixStream::pair().unwrap();
+        let sway = thread::spawn(move || {
+            let request = ipc_client::recv_message(&mut sway_fd, 1 << 20).unwrap();
+            assert_eq!(request.payload_type, IPCMessages::Subscribe as u32);
+            for message in [
+                IPCFormat::new(IPCMessages::Subscribe as u32).payload("{ \"success\": true }"),
+                // an event type this build does not know
+                IPCFormat::new((1 << 31) | 0x30).payload("{ }"),
+                IPCFormat::new(IPCEvents::Mode as u32).payload("{ \"change\": 7 }"),
+                IPCFormat::new(IPCEvents::Mode as u32)
+                    .payload("{ \"change\": \"resize\", \"pango_markup\": false }"),
+            ] {
+                ipc_client::send(&mut sway_fd, &message).unwrap();
+            }
+        });
+        let events = Subscription::new()
+            .event(IPCEvents::Mode)
+            .subscribe_on(fd)
+            .unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let reader = thread::spawn(move || read_events(events, sender, false));
+        sway.join().unwrap();
+
+        assert!(
+            matches!(receiver.recv().unwrap(), Ok(Event::Mode(mode)) if mode.change == "resize")
+        );
+        // sway closing the connection still ends the stream
+        assert!(matches!(
+            receiver.recv().unwrap(),
+            Err(IPCError::UnexpectedEof)
+        ));
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn duplicate_lines_are_skipped_unless_always_emit() {
+        let (fd, _sway_fd) = UnixStream::pair().unwrap();
+        let client = IPCClient::from_stream(fd);
+        for (always_emit, expected) in [
+            (false, "default\nresize\ndefault\n"),
+            (true, "default\nresize\nresize\ndefault\ndefault\n"),
+        ] {
+            let (sender, receiver) = mpsc::channel();
+            for name in ["resize", "resize", "default", "default"] {
+                sender.send(mode(name)).unwrap();
+            }
+            sender
+                .send(Ok(Event::Shutdown(ShutdownEvent {
+                    change: String::from("exit"),
+                })))
+                .unwrap();
+            let mut out = vec![];
+            let mut emitter = Emitter::new(&mut out, always_emit);
+            print_lines(
+                &mut ModeNames {},
+                &client,
+                &receiver,
+                Duration::ZERO,
+                None,
+                &mut emitter,
+            )
+            .unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        assert_eq!(backoff(0), Duration::from_millis(250));
+        assert_eq!(backoff(1), Duration::from_millis(500));
+        assert_eq!(backoff(5), Duration::from_secs(8));
+        assert_eq!(backoff(40), Duration::from_secs(8));
+    }
+}
@@ -1,14 +1,12 @@
-use std::env;
-use std::error::Error;
-use std::ffi::OsString;
-use std::io::{Read, Write};
-use std::os::unix::net::UnixStream;
-use std::thread;
+use std::{env, process};
 
 use eww_sway_ipc_backend::*;
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
     let args: Vec<String> = env::args().collect();
-    crate::run(&args);
-    Ok(())
+    // eww only logs stderr, so keep it to a single line explaining why the listener died
+    if let Err(e) = crate::run(&args) {
+        eprintln!("eww_sway_ipc_backend: {e}");
+        process::exit(1);
+    }
 }
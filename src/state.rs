@@ -0,0 +1,319 @@
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::ipc_types::{Node, Workspace, WorkspaceChange, WorkspaceEvent};
+
+// The workspace list as last seen, seeded from GET_WORKSPACES and then patched from the nodes
+// carried in workspace events so a focus change does not need another round trip to sway
+#[derive(Debug, Clone)]
+pub struct State {
+    workspaces: Vec<Workspace>,
+    synced_at: Instant,
+}
+
+impl State {
+    pub fn new(workspaces: Vec<Workspace>) -> State {
+        State {
+            workspaces,
+            synced_at: Instant::now(),
+        }
+    }
+
+    pub fn workspaces(&self) -> &[Workspace] {
+        &self.workspaces
+    }
+
+    // Time since the state was last replaced with a full GET_WORKSPACES reply
+    pub fn age(&self) -> Duration {
+        self.synced_at.elapsed()
+    }
+
+    // Takes the windows of every workspace from GET_TREE, see fill_apps
+    pub fn update_apps(&mut self, tree: &Node) {
+        fill_apps(&mut self.workspaces, tree);
+    }
+
+    // Patches the state from a workspace event. Returns false when the event cannot be applied
+    // and the state has to be synced again, eg. for a workspace it has never seen.
+    pub fn apply(&mut self, event: &WorkspaceEvent) -> bool {
+        let current = match &event.current {
+            Some(current) => current,
+            None => return false,
+        };
+        match event.change {
+            WorkspaceChange::Init => match workspace_from_node(current) {
+                Some(workspace) => {
+                    self.insert(workspace);
+                    true
+                }
+                None => false,
+            },
+            WorkspaceChange::Empty => {
+                let len = self.workspaces.len();
+                self.workspaces
+                    .retain(|workspace| workspace.id != current.id);
+                self.workspaces.len() != len
+            }
+            WorkspaceChange::Focus => self.focus(current),
+            WorkspaceChange::Urgent => match self.find(current.id) {
+                Some(workspace) => {
+                    workspace.urgent = current.urgent;
+                    true
+                }
+                None => false,
+            },
+            // these can reorder the list, sway's sorting is not worth duplicating for them
+            WorkspaceChange::Move | WorkspaceChange::Rename | WorkspaceChange::Reload => false,
+            WorkspaceChange::Unknown(_) => true,
+        }
+    }
+
+    fn find(&mut self, id: i64) -> Option<&mut Workspace> {
+        self.workspaces
+            .iter_mut()
+            .find(|workspace| workspace.id == id)
+    }
+
+    // The focused workspace becomes the visible one on its output, the previously focused
+    // workspace stays visible if it is on another output
+    fn focus(&mut self, current: &Node) -> bool {
+        let output = match self.find(current.id) {
+            Some(workspace) => workspace.output.clone(),
+            None => return false,
+        };
+        for workspace in &mut self.workspaces {
+            workspace.focused = workspace.id == current.id;
+            if workspace.focused {
+                workspace.visible = true;
+                workspace.urgent = current.urgent;
+            } else if workspace.output == output {
+                workspace.visible = false;
+            }
+        }
+        true
+    }
+
+    // Sway keeps the workspaces of an output sorted with numbered ones first by number, named
+    // ones keep the order they were created in
+    fn insert(&mut self, workspace: Workspace) {
+        let same_output = |other: &Workspace| other.output == workspace.output;
+        let position = self
+            .workspaces
+            .iter()
+            .position(|other| same_output(other) && cmp_workspaces(&workspace, other).is_lt())
+            .or_else(|| {
+                self.workspaces
+                    .iter()
+                    .rposition(same_output)
+                    .map(|last| last + 1)
+            })
+            .unwrap_or(self.workspaces.len());
+        self.workspaces.insert(position, workspace);
+    }
+}
+
+// Sets the apps of each workspace to those of its windows in the tree, in the order they appear
+pub fn fill_apps(workspaces: &mut [Workspace], tree: &Node) {
+    let nodes = tree.workspaces();
+    for workspace in workspaces {
+        workspace.apps.clear();
+        let windows = nodes
+            .iter()
+            .filter(|node| node.id == workspace.id)
+            .flat_map(|node| node.windows());
+        for app in windows.filter_map(Node::app) {
+            if !workspace.apps.iter().any(|other| other == app) {
+                workspace.apps.push(app.to_string());
+            }
+        }
+    }
+}
+
+pub(crate) fn cmp_workspaces(a: &Workspace, b: &Workspace) -> Ordering {
+    match (a.num >= 0, b.num >= 0) {
+        (true, true) => a.num.cmp(&b.num),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => Ordering::Equal,
+    }
+}
+
+// Workspace nodes carry everything GET_WORKSPACES does except visible and representation
+fn workspace_from_node(node: &Node) -> Option<Workspace> {
+    let name = node.name.clone()?;
+    Some(Workspace {
+        id: node.id,
+        num: node.num?,
+        label: name.clone(),
+        name,
+        output: node.output.clone()?,
+        focused: node.focused,
+        visible: node.focused,
+        urgent: node.urgent,
+        rect: node.rect.clone(),
+        representation: None,
+        focus: node.focus.clone(),
+        floating_nodes: node.floating_nodes.len(),
+        exists: true,
+        apps: vec![],
+        icons: vec![],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipc_types::fixtures::{self, workspace};
+    use crate::ipc_types::{self, Event};
+
+    fn event(change: &str, id: i64, name: &str, output: &str) -> WorkspaceEvent {
+        let workspace = Workspace {
+            id,
+            output: output.to_string(),
+            ..workspace(name, change == "focus", false)
+        };
+        match fixtures::workspace_event(change, &workspace) {
+            Event::Workspace(event) => *event,
+            e => panic!("expected a workspace event, got {e:?}"),
+        }
+    }
+
+    fn names(state: &State) -> Vec<(&str, bool, bool)> {
+        state
+            .workspaces()
+            .iter()
+            .map(|workspace| {
+                (
+                    workspace.name.as_str(),
+                    workspace.focused,
+                    workspace.visible,
+                )
+            })
+            .collect()
+    }
+
+    // 1 is focused, 3 is on the same output and 9 is visible on the other one
+    fn two_outputs() -> Vec<Workspace> {
+        vec![
+            Workspace {
+                id: 4,
+                ..workspace("1", true, false)
+            },
+            Workspace {
+                id: 5,
+                ..workspace("3", false, false)
+            },
+            Workspace {
+                id: 6,
+                visible: true,
+                output: String::from("HDMI-A-1"),
+                ..workspace("9", false, false)
+            },
+        ]
+    }
+
+    #[test]
+    fn focus_moves_visibility_within_an_output() {
+        let mut state = State::new(two_outputs());
+        assert!(state.apply(&event("focus", 5, "3", "eDP-1")));
+        assert_eq!(
+            names(&state),
+            vec![("1", false, false), ("3", true, true), ("9", false, true)]
+        );
+        // focusing the other output leaves this one's workspace visible
+        assert!(state.apply(&event("focus", 6, "9", "HDMI-A-1")));
+        assert_eq!(
+            names(&state),
+            vec![("1", false, false), ("3", false, true), ("9", true, true)]
+        );
+    }
+
+    #[test]
+    fn init_and_empty_add_and_remove_workspaces() {
+        let mut state = State::new(two_outputs());
+        assert!(state.apply(&event("init", 7, "2", "eDP-1")));
+        assert!(state.apply(&event("init", 8, "mail", "eDP-1")));
+        assert!(state.apply(&event("init", 9, "10", "HDMI-A-1")));
+        let order: Vec<&str> = state.workspaces().iter().map(|w| w.name.as_str()).collect();
+        assert_eq!(order, vec!["1", "2", "3", "mail", "9", "10"]);
+
+        assert!(state.apply(&event("empty", 8, "mail", "eDP-1")));
+        assert_eq!(state.workspaces().len(), 5);
+    }
+
+    #[test]
+    fn unknown_workspaces_need_a_resync() {
+        let mut state = State::new(two_outputs());
+        assert!(!state.apply(&event("focus", 42, "4", "eDP-1")));
+        assert!(!state.apply(&event("empty", 42, "4", "eDP-1")));
+        assert!(!state.apply(&event("rename", 4, "1: web", "eDP-1")));
+    }
+
+    fn node(
+        id: i64,
+        node_type: &str,
+        extra: &str,
+        nodes: &[String],
+        floating: &[String],
+    ) -> String {
+        format!("{{ \"id\": {id}, \"type\": \"{node_type}\", \"layout\": \"splith\", \"rect\": {{ \"x\": 0, \"y\": 0, \"width\": 1920, \"height\": 1080 }}, \"urgent\": false, \"focused\": false, \"marks\": [ ], \"focus\": [ ], {extra}\"nodes\": [ {} ], \"floating_nodes\": [ {} ] }}", nodes.join(", "), floating.join(", "))
+    }
+
+    #[test]
+    fn apps_are_taken_from_the_tree() {
+        let window = |id, extra: &str| node(id, "con", extra, &[], &[]);
+        let split = node(
+            10,
+            "con",
+            "",
+            &[
+                window(11, "\"app_id\": \"foot\", \"pid\": 1, "),
+                window(12, "\"app_id\": \"firefox\", \"pid\": 2, "),
+            ],
+            &[],
+        );
+        let gimp = node(
+            14,
+            "floating_con",
+            "\"window\": 99, \"window_properties\": { \"class\": \"Gimp\" }, ",
+            &[],
+            &[],
+        );
+        let first = node(
+            4,
+            "workspace",
+            "",
+            &[split, window(13, "\"app_id\": \"foot\", \"pid\": 3, ")],
+            &[gimp],
+        );
+        let second = node(5, "workspace", "", &[], &[]);
+        let third = node(
+            6,
+            "workspace",
+            "",
+            &[window(15, "\"app_id\": \"thunderbird\", \"pid\": 4, ")],
+            &[],
+        );
+        let outputs = [
+            node(2, "output", "", &[first, second], &[]),
+            node(3, "output", "", &[third], &[]),
+        ];
+        let tree: Node = ipc_types::from_json(&node(1, "root", "", &outputs, &[])).unwrap();
+
+        let mut state = State::new(two_outputs());
+        state.update_apps(&tree);
+        let apps: Vec<&Vec<String>> = state.workspaces().iter().map(|w| &w.apps).collect();
+        assert_eq!(
+            apps,
+            vec![
+                &vec![
+                    String::from("foot"),
+                    String::from("firefox"),
+                    String::from("Gimp")
+                ],
+                &vec![],
+                &vec![String::from("thunderbird")]
+            ]
+        );
+    }
+}
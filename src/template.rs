@@ -0,0 +1,363 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::ipc_types::Workspace;
+use crate::json_parser;
+
+// A small template language for printing the workspace list in whatever shape a widget wants:
+//   {field}               the value of a field
+//   {?field:then}         then if the field is true (or a non-empty string)
+//   {?field:then|else}    else otherwise, both may contain further placeholders
+//   \{ \} \| \\           literal characters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Field(String),
+    Conditional {
+        field: String,
+        then: Template,
+        otherwise: Template,
+    },
+}
+
+pub enum Value {
+    Text(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateError {
+    UnknownField(String),
+    // a { without its }
+    Unclosed,
+    UnexpectedClose,
+    // {?field} without a :
+    MissingColon(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::UnknownField(field) => write!(f, "unknown field \"{field}\""),
+            TemplateError::Unclosed => write!(f, "missing }}"),
+            TemplateError::UnexpectedClose => {
+                write!(f, "unexpected }}, use \\}} for a literal one")
+            }
+            TemplateError::MissingColon(field) => write!(f, "expected : after ?{field}"),
+        }
+    }
+}
+
+impl Template {
+    // fields lists the names that may be used, so typos are caught before anything is printed
+    pub fn parse(source: &str, fields: &[&str]) -> Result<Template, TemplateError> {
+        let mut chars = source.chars().peekable();
+        let (template, end) = parse_until(&mut chars, fields, false)?;
+        match end {
+            None => Ok(template),
+            Some(_) => Err(TemplateError::UnexpectedClose),
+        }
+    }
+
+    pub fn render(&self, lookup: &dyn Fn(&str) -> Value) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Field(field) => match lookup(field) {
+                    Value::Text(text) => out.push_str(&text),
+                    Value::Bool(value) => out.push_str(if value { "true" } else { "false" }),
+                },
+                Segment::Conditional {
+                    field,
+                    then,
+                    otherwise,
+                } => {
+                    let truthy = match lookup(field) {
+                        Value::Text(text) => !text.is_empty(),
+                        Value::Bool(value) => value,
+                    };
+                    let branch = if truthy { then } else { otherwise };
+                    out.push_str(&branch.render(lookup));
+                }
+            }
+        }
+        out
+    }
+
+    // Applies f to the literal text only, eg. to quote it for a format the field values are
+    // already quoted for
+    pub fn map_text(self, f: &dyn Fn(&str) -> String) -> Template {
+        let segments = self
+            .segments
+            .into_iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => Segment::Text(f(&text)),
+                Segment::Field(field) => Segment::Field(field),
+                Segment::Conditional {
+                    field,
+                    then,
+                    otherwise,
+                } => Segment::Conditional {
+                    field,
+                    then: then.map_text(f),
+                    otherwise: otherwise.map_text(f),
+                },
+            })
+            .collect();
+        Template { segments }
+    }
+}
+
+// Parses text and placeholders up to the end of the input, or inside a conditional up to the
+// | or } that ends the branch, which is returned along with the template
+fn parse_until(
+    chars: &mut Peekable<Chars>,
+    fields: &[&str],
+    in_conditional: bool,
+) -> Result<(Template, Option<char>), TemplateError> {
+    let mut segments = vec![];
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.push(chars.next().unwrap_or('\\')),
+            '{' => {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(parse_placeholder(chars, fields)?);
+            }
+            '|' | '}' if in_conditional => {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(text));
+                }
+                return Ok((Template { segments }, Some(c)));
+            }
+            '}' => return Err(TemplateError::UnexpectedClose),
+            c => text.push(c),
+        }
+    }
+    if in_conditional {
+        return Err(TemplateError::Unclosed);
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok((Template { segments }, None))
+}
+
+// Called after the opening {
+fn parse_placeholder(
+    chars: &mut Peekable<Chars>,
+    fields: &[&str],
+) -> Result<Segment, TemplateError> {
+    let conditional = chars.next_if_eq(&'?').is_some();
+    let mut field = String::new();
+    let end = loop {
+        match chars.next() {
+            Some(c @ ('}' | ':')) => break c,
+            Some(c) => field.push(c),
+            None => return Err(TemplateError::Unclosed),
+        }
+    };
+    if !fields.contains(&field.as_str()) {
+        return Err(TemplateError::UnknownField(field));
+    }
+    if !conditional {
+        return match end {
+            '}' => Ok(Segment::Field(field)),
+            _ => Err(TemplateError::UnknownField(format!("{field}:"))),
+        };
+    }
+    if end != ':' {
+        return Err(TemplateError::MissingColon(field));
+    }
+    let (then, end) = parse_until(chars, fields, true)?;
+    let otherwise = match end {
+        Some('|') => parse_until(chars, fields, true)?.0,
+        _ => Template { segments: vec![] },
+    };
+    Ok(Segment::Conditional {
+        field,
+        then,
+        otherwise,
+    })
+}
+
+pub const WORKSPACE_FIELDS: [&str; 12] = [
+    "id",
+    "name",
+    "label",
+    "num",
+    "output",
+    "focused",
+    "visible",
+    "urgent",
+    "representation",
+    "exists",
+    // separated by spaces
+    "apps",
+    "icons",
+];
+
+// items is every workspace rendered and joined, count the number of workspaces
+pub const WRAPPER_FIELDS: [&str; 2] = ["items", "count"];
+
+// Strings are decoded from the json sway sent, so web\/dev prints as web/dev
+pub fn workspace_field(workspace: &Workspace, field: &str) -> Value {
+    let text = |value: &str| Value::Text(json_parser::unescape(value));
+    match field {
+        "id" => Value::Text(workspace.id.to_string()),
+        "name" => text(&workspace.name),
+        "label" => text(&workspace.label),
+        "num" => Value::Text(workspace.num.to_string()),
+        "output" => text(&workspace.output),
+        "focused" => Value::Bool(workspace.focused),
+        "visible" => Value::Bool(workspace.visible),
+        "urgent" => Value::Bool(workspace.urgent),
+        "representation" => text(workspace.representation.as_deref().unwrap_or_default()),
+        "exists" => Value::Bool(workspace.exists),
+        "apps" => text(&workspace.apps.join(" ")),
+        "icons" => Value::Text(workspace.icons.join(" ")),
+        _ => Value::Text(String::new()),
+    }
+}
+
+// --template, --join and --wrap together
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceTemplate {
+    item: Template,
+    join: String,
+    wrap: Template,
+    // --escape json, for templates that print json and need the values quoted again
+    json: bool,
+}
+
+impl WorkspaceTemplate {
+    pub fn new(item: &str, join: &str, wrap: &str) -> Result<WorkspaceTemplate, TemplateError> {
+        Ok(WorkspaceTemplate {
+            item: Template::parse(item, &WORKSPACE_FIELDS)?,
+            join: join.to_string(),
+            wrap: Template::parse(wrap, &WRAPPER_FIELDS)?,
+            json: false,
+        })
+    }
+
+    pub fn json(mut self) -> WorkspaceTemplate {
+        self.json = true;
+        self
+    }
+
+    pub fn render(&self, workspaces: &[Workspace]) -> String {
+        let items: Vec<String> = workspaces
+            .iter()
+            .map(|workspace| {
+                self.item
+                    .render(&|field| match workspace_field(workspace, field) {
+                        Value::Text(text) if self.json => Value::Text(json_parser::escape(&text)),
+                        // a newline would end the line eww reads, so control characters print
+                        // as spaces
+                        Value::Text(text) => Value::Text(text.replace(char::is_control, " ")),
+                        value => value,
+                    })
+            })
+            .collect();
+        let items = items.join(&self.join);
+        self.wrap.render(&|field| match field {
+            "items" => Value::Text(items.clone()),
+            _ => Value::Text(workspaces.len().to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ipc_types::fixtures::workspace;
+
+    fn workspaces() -> Vec<Workspace> {
+        vec![workspace("1", true, false), workspace("mail", false, true)]
+    }
+
+    #[test]
+    fn renders_fields_conditionals_and_joins() {
+        let template = WorkspaceTemplate::new("{name}{?focused:*}", " ", "{items}").unwrap();
+        assert_eq!(template.render(&workspaces()), "1* mail");
+
+        let template = WorkspaceTemplate::new(
+            "{?urgent:!|{?focused:[{name}]|{name}}}",
+            "|",
+            "{count}: {items}",
+        )
+        .unwrap();
+        assert_eq!(template.render(&workspaces()), "2: [1]|!");
+
+        // literal braces for json or yuck
+        let template =
+            WorkspaceTemplate::new("\\{\"n\":{num},\"urgent\":{urgent}\\}", ",", "[{items}]")
+                .unwrap();
+        assert_eq!(
+            template.render(&workspaces()),
+            "[{\"n\":1,\"urgent\":false},{\"n\":-1,\"urgent\":true}]"
+        );
+    }
+
+    #[test]
+    fn values_are_decoded_and_escaped_again_for_json() {
+        let mut workspaces = workspaces();
+        workspaces[0].name = String::from("web\\/dev");
+        workspaces[1].name = String::from("caf\\u00e9 \\\"2\\\"");
+        let template = WorkspaceTemplate::new("{name}", " ", "{items}").unwrap();
+        assert_eq!(template.render(&workspaces), "web/dev café \"2\"");
+
+        let template = WorkspaceTemplate::new("\"{name}\"", ",", "[{items}]")
+            .unwrap()
+            .json();
+        assert_eq!(
+            template.render(&workspaces),
+            "[\"web/dev\",\"café \\\"2\\\"\"]"
+        );
+    }
+
+    #[test]
+    fn control_characters_do_not_break_the_line() {
+        let mut workspaces = workspaces();
+        workspaces[0].name = String::from("one\\ntwo\\tthree\\u0007");
+        let template = WorkspaceTemplate::new("{name}", " ", "{items}").unwrap();
+        assert_eq!(template.render(&workspaces), "one two three  mail");
+
+        let template = WorkspaceTemplate::new("\"{name}\"", ",", "[{items}]")
+            .unwrap()
+            .json();
+        assert_eq!(
+            template.render(&workspaces),
+            "[\"one\\ntwo\\tthree\\u0007\",\"mail\"]"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        let parse = |source| Template::parse(source, &WORKSPACE_FIELDS).unwrap_err();
+        assert_eq!(
+            parse("{nmae}"),
+            TemplateError::UnknownField(String::from("nmae"))
+        );
+        assert_eq!(parse("{name"), TemplateError::Unclosed);
+        assert_eq!(parse("{?focused:*"), TemplateError::Unclosed);
+        assert_eq!(parse("name}"), TemplateError::UnexpectedClose);
+        assert_eq!(
+            parse("{?focused}"),
+            TemplateError::MissingColon(String::from("focused"))
+        );
+        assert_eq!(
+            Template::parse("{name}", &WRAPPER_FIELDS).unwrap_err(),
+            TemplateError::UnknownField(String::from("name"))
+        );
+    }
+}